@@ -34,7 +34,9 @@ fn setup_svg(
     materials: ResMut<Assets<ColorMaterial>>,
     meshes: ResMut<Assets<Mesh>>,
 ) {
-    load_svg_map(commands, materials, meshes, "assets/ex.svg", CustomStrategy);
+    if let Err(e) = load_svg_map(commands, materials, meshes, "assets/ex.svg", CustomStrategy) {
+        error!("{}", e);
+    }
 }
 
 fn main() {
@@ -0,0 +1,844 @@
+//! Integration with the bevy `AssetServer`.
+use crate::{
+    build_bundles,
+    cache::SvgMapCache,
+    groups::GroupSegment,
+    render::{build_vertex_color_pipeline, VERTEX_COLOR_PIPELINE_HANDLE},
+    style::StyleSegment,
+    tokenize_svg,
+    transform::decompose,
+    MapOptions, MapTransform, StyleStrategy, SvgMapError, SvgStyle,
+};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::Command,
+    prelude::*,
+    reflect::TypeUuid,
+    render::pipeline::PipelineDescriptor,
+    utils::BoxedFuture,
+};
+use euclid::default::Transform2D;
+use lyon::{path::Path, svg::path_utils::build_path};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::Range,
+};
+
+/// Identity of an element of a map, used to match the elements of a reloaded document with
+/// the ones that were already spawned.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ElementKey {
+    /// The `id` attribute of the element
+    Id(String),
+    /// Hash of the path data and the transform of an element without (unique) id, and the
+    /// number of identical elements before it in the document
+    Content(u64, usize),
+}
+
+/// Part of an element that an entity draws.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PartKind {
+    Stroke,
+    Fill,
+}
+
+/// A drawable element of a [`SvgMap`]: its resolved style and its outline.
+#[derive(Debug)]
+pub struct SvgElement {
+    key: ElementKey,
+    style: SvgStyle,
+    path: Path,
+    parent: Option<usize>,
+    parts: Vec<(PartKind, f32)>,
+    /// Transformation from the user space of the element to the space of its parent entity
+    pub(crate) transform: Transform2D<f32>,
+}
+
+impl SvgElement {
+    pub fn key(&self) -> &ElementKey {
+        &self.key
+    }
+    pub fn style(&self) -> &SvgStyle {
+        &self.style
+    }
+    /// Outline of the element in the space of its parent entity (the group or the root)
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Index of the closest group that contains the element, see [`SvgMap::groups`]
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+    /// Parts of the element in paint order, with their z in the world
+    pub fn parts(&self) -> &[(PartKind, f32)] {
+        &self.parts
+    }
+}
+
+/// A `<g>` element of a [`SvgMap`], spawned as the parent of its elements and groups.
+#[derive(Debug)]
+pub struct SvgGroup {
+    key: ElementKey,
+    parent: Option<usize>,
+    transform: Transform,
+    layer: Option<SvgLayer>,
+    /// z of the entity in the world
+    pub(crate) z: f32,
+}
+
+impl SvgGroup {
+    pub fn key(&self) -> &ElementKey {
+        &self.key
+    }
+    /// Index of the closest group that contains this one, see [`SvgMap::groups`]
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+    /// Transform relative to the parent entity. Skewed transforms cannot be represented by
+    /// bevy, so they are applied to the outline of the elements instead and this is the
+    /// identity.
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+    /// The layer, if the group is an Inkscape layer
+    pub fn layer(&self) -> Option<&SvgLayer> {
+        self.layer.as_ref()
+    }
+}
+
+/// Builds the [`ElementKey`]s of a document.
+#[derive(Default)]
+struct Keys {
+    ids: HashSet<String>,
+    contents: HashMap<u64, usize>,
+}
+
+impl Keys {
+    fn key(&mut self, id: Option<&String>, content: impl Hash) -> ElementKey {
+        match id {
+            Some(id) if self.ids.insert(id.clone()) => ElementKey::Id(id.clone()),
+            _ => {
+                let mut hasher = DefaultHasher::new();
+                content.hash(&mut hasher);
+                let hash = hasher.finish();
+                let count = self.contents.entry(hash).or_insert(0);
+                *count += 1;
+                ElementKey::Content(hash, *count - 1)
+            }
+        }
+    }
+}
+
+fn transform_bits(transform: &Transform2D<f32>) -> [u32; 6] {
+    let mut bits = [0; 6];
+    for (b, x) in bits.iter_mut().zip(transform.to_array().iter()) {
+        *b = x.to_bits();
+    }
+    bits
+}
+
+/// A parsed SVG map, ready to be spawned with [`spawn_svg_map`](crate::spawn_svg_map).
+///
+/// It is the asset produced by the [`SvgMapPlugin`] for `.svg` files.
+#[derive(Debug, TypeUuid)]
+#[uuid = "fe8c0670-622f-4bc5-82cf-0b21d04e3964"]
+pub struct SvgMap {
+    map_transform: MapTransform,
+    groups: Vec<SvgGroup>,
+    elements: Vec<SvgElement>,
+}
+
+impl SvgMap {
+    /// Parse the source of a SVG document, placing it in the world as specified by the
+    /// [`MapOptions`].
+    pub fn parse(svg: &str, options: &MapOptions) -> Result<Self, SvgMapError> {
+        let (map_transform, group_segments, segments) = tokenize_svg(svg, options)?;
+        let to_world = map_transform.transform();
+        let from_world = to_world.inverse();
+        let mut keys = Keys::default();
+        // the parts are given consecutive slots of z, in paint order
+        let parts: Vec<Vec<PartKind>> = segments.iter().map(|s| painted(&s.style)).collect();
+        let mut slots = vec![0];
+        for p in &parts {
+            slots.push(slots[slots.len() - 1] + p.len());
+        }
+        let Range { start, end } = options.z_range;
+        let step = (end - start) / slots[parts.len()].max(1) as f32;
+        let slot_z = |slot: usize| start + slot as f32 * step;
+        // the elements of each layer, and the ones after it, are moved up by `layer_depth`
+        let layer_starts: Vec<usize> = group_segments
+            .iter()
+            .filter(|g| g.layer.is_some())
+            .map(|g| g.segments_before)
+            .collect();
+        let layer_z = |i: usize| {
+            let layer = layer_starts.partition_point(|&s| s <= i).saturating_sub(1);
+            layer as f32 * options.layer_depth
+        };
+        // the world frame of each group, as a transform of the user space of the document
+        let mut frames: Vec<Transform2D<f32>> = Vec::with_capacity(group_segments.len());
+        let mut layers = 0;
+        let mut groups: Vec<SvgGroup> = Vec::with_capacity(group_segments.len());
+        for GroupSegment {
+            id,
+            parent,
+            transform,
+            layer,
+            segments_before,
+        } in group_segments
+        {
+            let parent_frame = parent.map_or_else(Transform2D::identity, |p| frames[p]);
+            let parent_z = parent.map_or(0., |p| groups[p].z);
+            let layer = layer.map(|label| {
+                layers += 1;
+                SvgLayer {
+                    label,
+                    index: layers - 1,
+                }
+            });
+            // layers start at the z of their first part, other groups stay at their parent's
+            let z = match layer {
+                Some(ref layer) => {
+                    slot_z(slots[segments_before]) + layer.index as f32 * options.layer_depth
+                }
+                None => parent_z,
+            };
+            let local = from_world
+                .and_then(|from_world| decompose(&from_world.then(&transform).then(to_world)));
+            frames.push(match local {
+                Some(_) => transform.then(&parent_frame),
+                None => parent_frame,
+            });
+            let key = keys.key(
+                id.as_ref(),
+                (parent.map(|p| &groups[p].key), transform_bits(&transform)),
+            );
+            let mut transform = local.unwrap_or_default();
+            transform.translation.z = z - parent_z;
+            groups.push(SvgGroup {
+                key,
+                parent,
+                transform,
+                layer,
+                z,
+            });
+        }
+        let elements = segments
+            .into_iter()
+            .zip(parts)
+            .enumerate()
+            .map(
+                |(
+                    i,
+                    (
+                        StyleSegment {
+                            style,
+                            traces,
+                            transform,
+                            group,
+                        },
+                        parts,
+                    ),
+                )| {
+                    let key = keys.key(style.id().as_ref(), (&traces, transform_bits(&transform)));
+                    let frame = group.map_or_else(Transform2D::identity, |g| frames[g]);
+                    let transform = transform
+                        .then(&frame.inverse().unwrap_or_else(Transform2D::identity))
+                        .then(to_world);
+                    let builder = Path::builder().with_svg().transformed(transform);
+                    let path =
+                        build_path(builder, &traces).map_err(|_| SvgMapError::PathSyntax {
+                            id: style.id().clone(),
+                            message: path_error(&traces),
+                        })?;
+                    Ok(SvgElement {
+                        key,
+                        style,
+                        path,
+                        parent: group,
+                        parts: parts
+                            .into_iter()
+                            .enumerate()
+                            .map(|(j, kind)| (kind, slot_z(slots[i] + j) + layer_z(i)))
+                            .collect(),
+                        transform,
+                    })
+                },
+            )
+            .collect::<Result<_, SvgMapError>>()?;
+        Ok(SvgMap {
+            map_transform,
+            groups,
+            elements,
+        })
+    }
+
+    /// Mapping from the document to the world
+    pub fn map_transform(&self) -> &MapTransform {
+        &self.map_transform
+    }
+
+    /// Groups in document order, so parents come before their children
+    pub fn groups(&self) -> &[SvgGroup] {
+        &self.groups
+    }
+
+    /// Drawable elements in document order
+    pub fn elements(&self) -> &[SvgElement] {
+        &self.elements
+    }
+}
+
+/// Parts of an element that are painted, in paint order
+fn painted(style: &SvgStyle) -> Vec<PartKind> {
+    let order = style.paint_order();
+    order
+        .iter()
+        .copied()
+        .filter(|kind| match kind {
+            PartKind::Stroke => style.stroke().is_some(),
+            PartKind::Fill => style.fill().is_some(),
+        })
+        .collect()
+}
+
+/// lyon does not tell what went wrong with the path data, so ask svgtypes
+pub(crate) fn path_error(traces: &str) -> String {
+    svgtypes::PathParser::from(traces)
+        .find_map(|segment| segment.err())
+        .map_or_else(|| "malformed path".to_owned(), |e| e.to_string())
+}
+
+struct SvgMapLoader {
+    options: MapOptions,
+}
+
+impl AssetLoader for SvgMapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let map = SvgMap::parse(std::str::from_utf8(bytes)?, &self.options)?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["svg"]
+    }
+}
+
+/// Registers the [`SvgMap`] asset and its loader, so that maps can be loaded asynchronously
+/// with `asset_server.load("map.svg")`.
+///
+/// Spawn the map once it is loaded, when its `AssetEvent::Created` arrives, or let
+/// [`spawn_svg_maps`] do it (and respawn it when the file changes).
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_svg_map::{spawn_svg_map, StyleStrategy, SvgMap, SvgMapPlugin};
+///
+/// struct MyStrategy;
+/// impl StyleStrategy for MyStrategy {}
+///
+/// fn setup(asset_server: Res<AssetServer>, mut commands: Commands) {
+///     let map: Handle<SvgMap> = asset_server.load("ex.svg");
+///     // keep the handle alive, or the map is unloaded
+///     commands.insert_resource(map);
+/// }
+///
+/// fn spawn(
+///     mut commands: Commands,
+///     mut events: EventReader<AssetEvent<SvgMap>>,
+///     maps: Res<Assets<SvgMap>>,
+///     mut materials: ResMut<Assets<ColorMaterial>>,
+///     mut meshes: ResMut<Assets<Mesh>>,
+/// ) {
+///     for event in events.iter() {
+///         if let AssetEvent::Created { handle } = event {
+///             let map = maps.get(handle).unwrap();
+///             let spawned =
+///                 spawn_svg_map(&mut commands, &mut materials, &mut meshes, map, &MyStrategy);
+///             if let Err(e) = spawned {
+///                 error!("{}", e);
+///             }
+///         }
+///     }
+/// }
+///
+/// App::build()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugin(SvgMapPlugin::default())
+///     .add_startup_system(setup.system())
+///     .add_system(spawn.system())
+///     .run();
+/// ```
+#[derive(Default)]
+pub struct SvgMapPlugin {
+    /// How the maps loaded by the plugin are placed in the world
+    pub options: MapOptions,
+    /// Watch the asset folder and reload the maps when their files change. It requires the
+    /// `filesystem_watcher` feature of bevy.
+    pub hot_reload: bool,
+}
+
+impl Plugin for SvgMapPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<SvgMap>().add_asset_loader(SvgMapLoader {
+            options: self.options.clone(),
+        });
+        let world = app.world_mut();
+        let pipeline = world
+            .get_resource_mut::<Assets<Shader>>()
+            .map(|mut shaders| build_vertex_color_pipeline(&mut shaders));
+        // without the RenderPlugin (e.g. a headless app) there is nothing to draw
+        if let Some(pipeline) = pipeline {
+            world
+                .get_resource_mut::<Assets<PipelineDescriptor>>()
+                .expect("the RenderPlugin must be added before the SvgMapPlugin")
+                .set_untracked(VERTEX_COLOR_PIPELINE_HANDLE, pipeline);
+        }
+        if self.hot_reload {
+            app.world()
+                .get_resource::<AssetServer>()
+                .expect("the AssetPlugin must be added before the SvgMapPlugin")
+                .watch_for_changes()
+                .unwrap();
+        }
+    }
+}
+
+/// Root entity of a spawned map, the parent of its top-level groups and elements.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SvgMapRoot;
+
+/// Marks the entities of the groups of a map. `instance` is the [`SvgMapRoot`] of the map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgMapGroup {
+    pub instance: Entity,
+    /// Group of the map
+    pub key: ElementKey,
+}
+
+/// Marks the entities that draw the elements of a map. `instance` is the [`SvgMapRoot`] of the
+/// map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgMapPart {
+    pub instance: Entity,
+    /// Element of the map drawn by the entity
+    pub key: ElementKey,
+    pub kind: PartKind,
+}
+
+/// Entity that draws the merged geometry of the batched parts of a map with the same material,
+/// see [`StyleStrategy::batch_decider`]. `instance` is the [`SvgMapRoot`] of the map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SvgMapBatch {
+    pub instance: Entity,
+}
+
+/// An Inkscape layer (a group with `inkscape:groupmode="layer"`), added to the entity of the
+/// group. Layers are placed in z at their first part, see [`MapOptions::z_range`], so later
+/// layers are drawn on top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgLayer {
+    /// The `inkscape:label` of the layer, or its id if it has no label
+    pub label: String,
+    /// Position of the layer among all the layers of the map, in document order
+    pub index: usize,
+}
+
+/// Makes `child` a child of `parent`. Unlike `push_children`, it removes the child from the
+/// `Children` of its previous parent and does nothing if the parent does not change.
+struct SetParent {
+    child: Entity,
+    parent: Entity,
+}
+
+impl Command for SetParent {
+    fn write(self: Box<Self>, world: &mut World) {
+        match world.get::<Parent>(self.child).map(|p| p.0) {
+            Some(previous) if previous == self.parent => return,
+            Some(previous) => {
+                if let Some(children) = world.get::<Children>(previous) {
+                    let children: Vec<Entity> = children
+                        .iter()
+                        .copied()
+                        .filter(|&c| c != self.child)
+                        .collect();
+                    world.entity_mut(previous).insert(Children::with(&children));
+                }
+            }
+            None => {}
+        }
+        world.entity_mut(self.parent).push_children(&[self.child]);
+    }
+}
+
+/// Entities of a map that has already been spawned, by key.
+#[derive(Default)]
+pub(crate) struct Spawned {
+    pub groups: HashMap<ElementKey, Entity>,
+    pub parts: HashMap<(ElementKey, PartKind), Entity>,
+    pub batches: Vec<Entity>,
+}
+
+/// What is spawned for a part of an element.
+pub(crate) enum PartBundle {
+    Sprite(Box<SpriteBundle>),
+    /// The part is drawn by a batch, its entity only has a transform
+    Batched(Transform),
+}
+
+/// Everything that is spawned for a map.
+pub(crate) struct Bundles<'a> {
+    pub parts: Vec<(&'a SvgElement, PartKind, PartBundle)>,
+    /// Merged geometry of the batched parts, relative to the root
+    pub batches: Vec<SpriteBundle>,
+}
+
+/// Spawn the hierarchy of the map under `root`, reusing the entities in `spawned` for the
+/// groups and elements with the same key, and despawning the rest of them.
+pub(crate) fn sync_map<T: StyleStrategy>(
+    commands: &mut Commands,
+    root: Entity,
+    map: &SvgMap,
+    bundles: Bundles,
+    strategy: &T,
+    mut spawned: Spawned,
+) {
+    let mut groups = Vec::with_capacity(map.groups().len());
+    for group in map.groups() {
+        let mut entity = match spawned.groups.remove(group.key()) {
+            Some(entity) => {
+                let mut entity = commands.entity(entity);
+                entity.insert(group.transform());
+                entity
+            }
+            None => {
+                let mut entity = commands.spawn();
+                entity.insert_bundle((
+                    group.transform(),
+                    GlobalTransform::default(),
+                    SvgMapGroup {
+                        instance: root,
+                        key: group.key().clone(),
+                    },
+                ));
+                entity
+            }
+        };
+        match group.layer() {
+            Some(layer) => entity.insert(layer.clone()),
+            None => entity.remove::<SvgLayer>(),
+        };
+        let entity = entity.id();
+        let parent = group.parent().map_or(root, |p| groups[p]);
+        commands.add(SetParent {
+            child: entity,
+            parent,
+        });
+        groups.push(entity);
+    }
+    for (element, kind, bundle) in bundles.parts {
+        let reused = spawned.parts.remove(&(element.key().clone(), kind));
+        let mut entity = match reused {
+            Some(entity) => commands.entity(entity),
+            None => {
+                let mut entity = commands.spawn();
+                entity.insert(SvgMapPart {
+                    instance: root,
+                    key: element.key().clone(),
+                    kind,
+                });
+                entity
+            }
+        };
+        match bundle {
+            PartBundle::Sprite(bundle) => {
+                entity.insert_bundle(*bundle);
+            }
+            PartBundle::Batched(transform) => {
+                // a reused entity may have been drawn by itself before
+                entity
+                    .remove_bundle::<SpriteBundle>()
+                    .insert_bundle((transform, GlobalTransform::default()));
+            }
+        }
+        if reused.is_some() {
+            strategy.component_reload_decider(element.style(), &mut entity);
+        } else {
+            strategy.component_decider(element.style(), &mut entity);
+        }
+        let child = entity.id();
+        let parent = element.parent().map_or(root, |g| groups[g]);
+        commands.add(SetParent { child, parent });
+    }
+    for (_, entity) in spawned.parts {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in spawned.batches {
+        commands.entity(entity).despawn_recursive();
+    }
+    for bundle in bundles.batches {
+        let child = commands
+            .spawn_bundle(bundle)
+            .insert(SvgMapBatch { instance: root })
+            .id();
+        commands.add(SetParent {
+            child,
+            parent: root,
+        });
+    }
+    // the surviving children of removed groups have already been moved to their new parents
+    for (_, entity) in spawned.groups {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// System that spawns the map of every entity with a `Handle<SvgMap>` using the strategy `T`,
+/// which must be available as a resource.
+///
+/// The entity with the handle becomes the [`SvgMapRoot`] of the map, with the groups and
+/// elements of the map as its descendants.
+///
+/// When the map is reloaded (see [`SvgMapPlugin::hot_reload`]), the groups and elements of the new
+/// document are matched with the spawned ones by their [`ElementKey`]. The entities of the
+/// elements that are still there are kept, with their components, and only get a new mesh and
+/// material; the strategy is then asked for their `component_reload_decider` instead of their
+/// `component_decider`, which only runs for new entities. Removed elements are despawned
+/// and new ones spawned. If the new document cannot be spawned, the error is logged and the old
+/// parts are kept.
+///
+/// If there is a [`SvgMapCache`] resource, the maps share their materials and meshes through it.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_svg_map::{spawn_svg_maps, StyleStrategy, SvgMap, SvgMapPlugin};
+///
+/// struct MyStrategy;
+/// impl StyleStrategy for MyStrategy {}
+///
+/// fn setup(asset_server: Res<AssetServer>, mut commands: Commands) {
+///     let map: Handle<SvgMap> = asset_server.load("ex.svg");
+///     commands.spawn().insert(map);
+/// }
+///
+/// App::build()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugin(SvgMapPlugin {
+///         hot_reload: true,
+///         ..Default::default()
+///     })
+///     .insert_resource(MyStrategy)
+///     .add_startup_system(setup.system())
+///     .add_system(spawn_svg_maps::<MyStrategy>.system())
+///     .run();
+/// ```
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn spawn_svg_maps<T: StyleStrategy + Send + Sync + 'static>(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<SvgMap>>,
+    maps: Res<Assets<SvgMap>>,
+    strategy: Res<T>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    instances: Query<(
+        Entity,
+        &Handle<SvgMap>,
+        ChangeTrackers<Handle<SvgMap>>,
+        Option<&Transform>,
+    )>,
+    groups: Query<(Entity, &SvgMapGroup)>,
+    parts: Query<(Entity, &SvgMapPart)>,
+    batches: Query<(Entity, &SvgMapBatch)>,
+    mut shared_cache: Option<ResMut<SvgMapCache>>,
+) {
+    let changed: HashSet<Handle<SvgMap>> = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                Some(handle.clone_weak())
+            }
+            AssetEvent::Removed { .. } => None,
+        })
+        .collect();
+    for (instance, handle, tracker, transform) in instances.iter() {
+        if !(tracker.is_added() || tracker.is_changed() || changed.contains(handle)) {
+            continue;
+        }
+        // not loaded yet, it will be spawned on its `Created` event
+        let map = match maps.get(handle) {
+            Some(map) => map,
+            None => continue,
+        };
+        let mut own_cache = SvgMapCache::default();
+        let cache = match shared_cache {
+            Some(ref mut cache) => &mut **cache,
+            None => &mut own_cache,
+        };
+        let bundles = match build_bundles(&mut materials, &mut meshes, cache, map, &*strategy) {
+            Ok(bundles) => bundles,
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        };
+        let mut root = commands.entity(instance);
+        root.insert(SvgMapRoot);
+        if transform.is_none() {
+            root.insert_bundle((Transform::default(), GlobalTransform::default()));
+        }
+        let spawned = Spawned {
+            groups: groups
+                .iter()
+                .filter(|(_, group)| group.instance == instance)
+                .map(|(entity, group)| (group.key.clone(), entity))
+                .collect(),
+            parts: parts
+                .iter()
+                .filter(|(_, part)| part.instance == instance)
+                .map(|(entity, part)| ((part.key.clone(), part.kind), entity))
+                .collect(),
+            batches: batches
+                .iter()
+                .filter(|(_, batch)| batch.instance == instance)
+                .map(|(entity, _)| entity)
+                .collect(),
+        };
+        sync_map(&mut commands, instance, map, bundles, &*strategy, spawned);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_map() {
+        let map = SvgMap::parse(
+            r#"<svg width="20" height="20"><g transform="translate(10 0)">
+                <rect id="r" width="10" height="10" fill="red"/></g></svg>"#,
+            &MapOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(map.elements().len(), 1);
+        let element = &map.elements()[0];
+        assert_eq!(element.style().id().as_deref(), Some("r"));
+        assert_eq!(element.parent(), Some(0));
+        // the outline is relative to the group, which carries the translation
+        assert_eq!(
+            element.path().iter().next().unwrap().from(),
+            lyon::math::point(-10., 10.)
+        );
+        assert_eq!(
+            map.groups()[0].transform().translation,
+            Vec3::new(10., 0., 0.)
+        );
+    }
+
+    #[test]
+    fn skewed_groups_are_baked() {
+        let map = SvgMap::parse(
+            r#"<svg width="20" height="20"><g transform="skewX(45)">
+                <path d="M 0 10 H 1"/></g></svg>"#,
+            &MapOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(map.groups()[0].transform(), Transform::identity());
+        // (0, 10) is moved to (10, 10) by the skew, the center of the world
+        assert_eq!(
+            map.elements()[0].path().iter().next().unwrap().from(),
+            lyon::math::point(0., 0.)
+        );
+    }
+
+    #[test]
+    fn element_keys() {
+        let map = SvgMap::parse(
+            r#"<svg><path id="a" d="M0 0 H 1"/><path id="a" d="M0 0 H 1"/>
+                <path d="M0 0 H 1"/><path d="M0 0 H 2"/></svg>"#,
+            &MapOptions::default(),
+        )
+        .unwrap();
+        let keys: Vec<&ElementKey> = map.elements().iter().map(SvgElement::key).collect();
+        assert_eq!(keys[0], &ElementKey::Id("a".to_owned()));
+        // a duplicated id falls back to the content
+        assert!(matches!(keys[1], ElementKey::Content(_, 0)));
+        assert!(matches!(keys[2], ElementKey::Content(_, 1)));
+        assert!(matches!(keys[3], ElementKey::Content(_, 0)));
+        assert_ne!(keys[1], keys[3]);
+    }
+
+    #[test]
+    fn layers_are_stacked() {
+        let map = SvgMap::parse(
+            r#"<svg xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+                <g inkscape:groupmode="layer" inkscape:label="Floor">
+                    <rect width="1" height="1" fill="red" stroke="blue"/></g>
+                <g inkscape:groupmode="layer" id="walls"><g>
+                    <path d="M0 0 H 1" stroke="red" fill="none"/>
+                    <g inkscape:groupmode="layer" inkscape:label="Doors">
+                        <path d="M0 0 H 1" stroke="red" fill="none"/></g></g></g></svg>"#,
+            &MapOptions {
+                z_range: 0.0..4.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let layer = |i: usize| map.groups()[i].layer().cloned();
+        assert_eq!(layer(0).unwrap().label, "Floor");
+        assert_eq!(layer(1).unwrap().label, "walls");
+        assert_eq!(layer(2), None);
+        assert_eq!(layer(3).unwrap().index, 2);
+        // z is relative to the parent entity
+        let z: Vec<f32> = map
+            .groups()
+            .iter()
+            .map(|g| g.transform().translation.z)
+            .collect();
+        assert_eq!(z, vec![0., 2., 0., 1.]);
+        assert_eq!(map.elements()[2].style().layer().as_deref(), Some("Doors"));
+    }
+
+    #[test]
+    fn layer_depth_separates_layers() {
+        let map = SvgMap::parse(
+            r#"<svg xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+                <g inkscape:groupmode="layer"><path d="M0 0 H 1" stroke="red"/></g>
+                <g inkscape:groupmode="layer"><path d="M0 0 H 1" stroke="red"/></g>
+                <path d="M0 0 H 1" stroke="red"/></svg>"#,
+            &MapOptions {
+                z_range: 0.0..3.0,
+                layer_depth: 10.,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let z: Vec<f32> = map.elements().iter().map(|e| e.parts()[0].1).collect();
+        assert_eq!(z, vec![0., 11., 12.]);
+        assert_eq!(map.groups()[1].transform().translation.z, 11.);
+    }
+
+    #[test]
+    fn paint_order_z() {
+        let map = SvgMap::parse(
+            r#"<svg><rect width="1" height="1" fill="red" stroke="blue"/>
+                <rect width="1" height="1" fill="red" stroke="blue" paint-order="stroke"/>
+                <rect width="1" height="1" fill="red"/></svg>"#,
+            &MapOptions {
+                z_range: 1.0..2.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let parts: Vec<&[(PartKind, f32)]> = map.elements().iter().map(|e| e.parts()).collect();
+        assert_eq!(parts[0], &[(PartKind::Fill, 1.), (PartKind::Stroke, 1.2)]);
+        assert_eq!(parts[1], &[(PartKind::Stroke, 1.4), (PartKind::Fill, 1.6)]);
+        assert_eq!(parts[2], &[(PartKind::Fill, 1.8)]);
+    }
+}
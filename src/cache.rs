@@ -0,0 +1,67 @@
+//! Sharing of the materials and meshes of the maps.
+use bevy::prelude::*;
+use lyon::tessellation::TessellationError;
+use std::collections::HashMap;
+
+/// Handles of the materials and meshes created for the maps, so that the parts with the same
+/// color share a material and the parts with the same geometry (e.g. copies of a prop) share
+/// a mesh.
+///
+/// Each map gets its own cache when spawned. Insert a `SvgMapCache` resource to share it
+/// between all the maps spawned by [`spawn_svg_maps`](crate::spawn_svg_maps) and between
+/// reloads. The cache holds strong handles, so call [`SvgMapCache::clear`] to let the assets
+/// that are no longer used be unloaded.
+#[derive(Debug, Default)]
+pub struct SvgMapCache {
+    materials: HashMap<[u32; 4], Handle<ColorMaterial>>,
+    /// By the events of the outline, the options of the tessellation and the vertex color,
+    /// as bits, so that different geometries never share a mesh
+    meshes: HashMap<Vec<u32>, Handle<Mesh>>,
+}
+
+impl SvgMapCache {
+    /// Handle of a material of the given color, added to the assets if it is not cached yet
+    pub(crate) fn material(
+        &mut self,
+        materials: &mut Assets<ColorMaterial>,
+        color: Color,
+    ) -> Handle<ColorMaterial> {
+        let [r, g, b, a] = color.as_rgba_f32();
+        self.materials
+            .entry([r.to_bits(), g.to_bits(), b.to_bits(), a.to_bits()])
+            .or_insert_with(|| materials.add(color.into()))
+            .clone()
+    }
+
+    /// Handle of the mesh with the given geometry key, built and added to the assets if it
+    /// is not cached yet
+    pub(crate) fn mesh(
+        &mut self,
+        meshes: &mut Assets<Mesh>,
+        geometry: Vec<u32>,
+        build: impl FnOnce() -> Result<Mesh, TessellationError>,
+    ) -> Result<Handle<Mesh>, TessellationError> {
+        if let Some(handle) = self.meshes.get(&geometry) {
+            return Ok(handle.clone());
+        }
+        let handle = meshes.add(build()?);
+        self.meshes.insert(geometry, handle.clone());
+        Ok(handle)
+    }
+
+    /// Number of distinct materials in the cache
+    pub fn materials(&self) -> usize {
+        self.materials.len()
+    }
+
+    /// Number of distinct meshes in the cache
+    pub fn meshes(&self) -> usize {
+        self.meshes.len()
+    }
+
+    /// Forget all the handles
+    pub fn clear(&mut self) {
+        self.materials.clear();
+        self.meshes.clear();
+    }
+}
@@ -0,0 +1,147 @@
+//! CSS colors: keywords, `#RGB[A]`, `#RRGGBB[AA]`, `rgb[a]()` and `hsl[a]()`, with both the
+//! comma and the space separated syntax.
+//!
+//! CSS colors are in the sRGB color space, like the colors built by `Color::rgba`; they are
+//! only converted to linear RGB where the GPU needs it (vertex colors).
+use bevy::prelude::Color;
+use std::str::FromStr;
+
+/// Parse a CSS color, `None` if it is not one (e.g. `none` or `url(#id)`)
+pub(crate) fn parse_color(text: &str) -> Option<Color> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("transparent") {
+        return Some(Color::NONE);
+    }
+    if let Some(hex) = text.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    if let Some(open) = text.find('(') {
+        let args = text[open + 1..].strip_suffix(')')?;
+        return match text[..open].trim().to_ascii_lowercase().as_str() {
+            "rgb" | "rgba" => parse_rgb(args),
+            "hsl" | "hsla" => parse_hsl(args),
+            _ => None,
+        };
+    }
+    // the named colors of SVG
+    svgtypes::Color::from_str(text)
+        .ok()
+        .map(|c| Color::rgb_u8(c.red, c.green, c.blue))
+}
+
+/// Parse an opacity, a finite number or percentage, clamped to `[0, 1]`
+pub(crate) fn parse_alpha(text: &str) -> Option<f32> {
+    let text = text.trim();
+    let alpha = match text.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f32>().ok()? / 100.,
+        None => text.parse::<f32>().ok()?,
+    };
+    // Rust also parses "NaN" and "inf", which are not numbers in CSS
+    if !alpha.is_finite() {
+        return None;
+    }
+    Some(alpha.clamp(0., 1.))
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..=i], 16).ok().map(|d| d * 17);
+    let pair = |i: usize| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok();
+    let [r, g, b, a] = match hex.len() {
+        3 => [digit(0)?, digit(1)?, digit(2)?, 255],
+        4 => [digit(0)?, digit(1)?, digit(2)?, digit(3)?],
+        6 => [pair(0)?, pair(1)?, pair(2)?, 255],
+        8 => [pair(0)?, pair(1)?, pair(2)?, pair(3)?],
+        _ => return None,
+    };
+    Some(Color::rgba_u8(r, g, b, a))
+}
+
+/// The three components and the alpha of a color function, e.g. `255, 0, 0, 0.5` or
+/// `255 0 0 / 50%`
+fn arguments(args: &str) -> Option<([&str; 3], f32)> {
+    let (components, alpha): (Vec<&str>, _) = if args.contains(',') {
+        let mut parts: Vec<&str> = args.split(',').map(str::trim).collect();
+        let alpha = if parts.len() == 4 { parts.pop() } else { None };
+        (parts, alpha)
+    } else {
+        let (components, alpha) = match args.find('/') {
+            Some(slash) => (&args[..slash], Some(args[slash + 1..].trim())),
+            None => (args, None),
+        };
+        (components.split_whitespace().collect(), alpha)
+    };
+    let alpha = match alpha {
+        Some(alpha) => parse_alpha(alpha)?,
+        None => 1.,
+    };
+    match components.as_slice() {
+        [a, b, c] => Some(([a, b, c], alpha)),
+        _ => None,
+    }
+}
+
+fn parse_rgb(args: &str) -> Option<Color> {
+    let ([r, g, b], alpha) = arguments(args)?;
+    let channel = |text: &str| {
+        let value = match text.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? / 100.,
+            None => text.parse::<f32>().ok()? / 255.,
+        };
+        Some(value.clamp(0., 1.))
+    };
+    Some(Color::rgba(channel(r)?, channel(g)?, channel(b)?, alpha))
+}
+
+fn parse_hsl(args: &str) -> Option<Color> {
+    let ([h, s, l], alpha) = arguments(args)?;
+    let hue = ["deg", "grad", "rad", "turn"]
+        .iter()
+        .zip([1., 0.9, 180. / std::f32::consts::PI, 360.].iter())
+        .find_map(|(unit, scale)| Some(h.strip_suffix(unit)?.parse::<f32>().ok()? * scale))
+        .or_else(|| h.parse().ok())?;
+    let percent =
+        |text: &str| Some((text.strip_suffix('%')?.parse::<f32>().ok()? / 100.).clamp(0., 1.));
+    Some(Color::hsla(hue.rem_euclid(360.), percent(s)?, percent(l)?, alpha).as_rgba())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_syntaxes() {
+        let red = Some(Color::RED);
+        assert_eq!(parse_color("red"), red);
+        assert_eq!(parse_color("#f00"), red);
+        assert_eq!(parse_color("#FF0000"), red);
+        assert_eq!(parse_color("rgb(255, 0, 0)"), red);
+        assert_eq!(parse_color("rgb(100% 0% 0%)"), red);
+        assert_eq!(parse_color("hsl(0, 100%, 50%)"), red);
+        assert_eq!(parse_color("hsl(1turn 100% 50%)"), red);
+        assert_eq!(parse_color("transparent"), Some(Color::NONE));
+        assert_eq!(parse_color("none"), None);
+        assert_eq!(parse_color("rgb(255, 0)"), None);
+    }
+
+    #[test]
+    fn colors_with_alpha() {
+        let alpha = |text: &str| parse_color(text).map(|c| c.a());
+        assert_eq!(alpha("#f008"), Some(136. / 255.));
+        assert_eq!(alpha("#ff000080"), Some(128. / 255.));
+        assert_eq!(alpha("rgba(255, 0, 0, 0.5)"), Some(0.5));
+        assert_eq!(alpha("rgb(255 0 0 / 25%)"), Some(0.25));
+        assert_eq!(alpha("hsla(120, 100%, 50%, 2)"), Some(1.));
+        assert_eq!(alpha("rgba(255, 0, 0, NaN)"), None);
+    }
+
+    #[test]
+    fn alpha_is_finite() {
+        assert_eq!(parse_alpha("NaN"), None);
+        assert_eq!(parse_alpha("inf%"), None);
+        assert_eq!(parse_alpha("-infinity"), None);
+        assert_eq!(parse_alpha(" 1e3 "), Some(1.));
+    }
+}
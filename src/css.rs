@@ -0,0 +1,652 @@
+//! Minimal CSS support to resolve the style of an element.
+//!
+//! The style comes from three sources, in increasing order of precedence: presentation
+//! attributes (`fill="red"`), the rules of the `<style>` elements and the `style` attribute.
+//! Inheritable properties set on groups are then passed down the tree, and the `opacity` of
+//! the groups is multiplied into the one of their content.
+//!
+//! Only simple selectors are supported (type, class, id, `*` and compounds like `path.wall`);
+//! rules with any other selector (descendants, pseudo-classes...) and at-rules are ignored.
+
+use crate::color::parse_alpha;
+use crate::error::StyleError;
+use crate::units::{Axis, Units};
+use std::{cell::RefCell, collections::HashMap, rc::Rc, str::FromStr};
+use svgtypes::{Length, LengthUnit};
+
+/// Attributes that are equivalent to the CSS property of the same name.
+/// See: https://www.w3.org/TR/SVG11/styling.html#SVGStylingProperties
+const PRESENTATION_ATTRIBUTES: &[&str] = &[
+    "clip-rule",
+    "color",
+    "display",
+    "fill",
+    "fill-opacity",
+    "fill-rule",
+    "font-size",
+    "opacity",
+    "paint-order",
+    "stop-color",
+    "stop-opacity",
+    "stroke",
+    "stroke-dasharray",
+    "stroke-dashoffset",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-miterlimit",
+    "stroke-opacity",
+    "stroke-width",
+    "visibility",
+];
+
+/// Properties that an element takes from its parent when it does not set them, with the
+/// initial value that the root element takes.
+const INHERITED_PROPERTIES: &[(&str, &str)] = &[
+    ("clip-rule", "nonzero"),
+    ("color", "black"),
+    ("fill", "black"),
+    ("fill-opacity", "1"),
+    ("fill-rule", "nonzero"),
+    ("font-size", "medium"),
+    ("paint-order", "normal"),
+    ("stroke", "none"),
+    ("stroke-dasharray", "none"),
+    ("stroke-dashoffset", "0"),
+    ("stroke-linecap", "butt"),
+    ("stroke-linejoin", "miter"),
+    ("stroke-miterlimit", "4"),
+    ("stroke-opacity", "1"),
+    ("stroke-width", "1"),
+    ("visibility", "visible"),
+];
+
+/// A `property: value` pair of a declaration block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declaration {
+    /// Name of the property, in lowercase
+    pub name: String,
+    pub value: String,
+    pub important: bool,
+}
+
+/// Parse a declaration block following the tokenization of CSS: `;` and `:` inside strings,
+/// comments and parentheses (e.g. `url(data:image/png;base64,...)`) do not split the
+/// declarations. The declarations that cannot be parsed are skipped and reported, empty
+/// ones (e.g. after a trailing `;`) are ignored.
+pub fn parse_declarations(block: &str) -> (Vec<Declaration>, Vec<StyleError>) {
+    let mut declarations = Vec::new();
+    let mut errors = Vec::new();
+    let mut finish = |text: &mut String, colon: &mut Option<usize>, unterminated: &mut bool| {
+        match declaration(text, *colon, *unterminated) {
+            Some(Ok(declaration)) => declarations.push(declaration),
+            Some(Err(error)) => errors.push(error),
+            None => {}
+        }
+        text.clear();
+        *colon = None;
+        *unterminated = false;
+    };
+    // the current declaration without its comments, and the position of its first `:`
+    let mut text = String::new();
+    let mut colon = None;
+    let mut unterminated = false;
+    let mut depth = 0usize;
+    let mut chars = block.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = None;
+                unterminated |= !chars.any(|c| {
+                    let end = previous == Some('*') && c == '/';
+                    previous = Some(c);
+                    end
+                });
+                text.push(' ');
+            }
+            '"' | '\'' => {
+                text.push(c);
+                let mut closed = false;
+                while let Some(s) = chars.next() {
+                    text.push(s);
+                    if s == '\\' {
+                        text.extend(chars.next());
+                    } else if s == c {
+                        closed = true;
+                        break;
+                    }
+                }
+                unterminated |= !closed;
+            }
+            '(' | '[' | '{' => {
+                depth += 1;
+                text.push(c);
+            }
+            ')' | ']' | '}' => {
+                depth = depth.saturating_sub(1);
+                text.push(c);
+            }
+            ':' if depth == 0 && colon.is_none() => {
+                colon = Some(text.len());
+                text.push(c);
+            }
+            ';' if depth == 0 => finish(&mut text, &mut colon, &mut unterminated),
+            _ => text.push(c),
+        }
+    }
+    unterminated |= depth > 0;
+    finish(&mut text, &mut colon, &mut unterminated);
+    (declarations, errors)
+}
+
+/// A single declaration, `None` if it is blank
+fn declaration(
+    text: &str,
+    colon: Option<usize>,
+    unterminated: bool,
+) -> Option<Result<Declaration, StyleError>> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let invalid = || Some(Err(StyleError::InvalidDeclaration(trimmed.to_owned())));
+    let colon = match colon {
+        Some(colon) => colon,
+        None => return invalid(),
+    };
+    let name = text[..colon].trim();
+    if !is_ident(name) {
+        return invalid();
+    }
+    if unterminated {
+        return Some(Err(StyleError::Unterminated(trimmed.to_owned())));
+    }
+    let mut value = text[colon + 1..].trim();
+    let important = match value.rfind('!') {
+        Some(bang) if value[bang + 1..].trim().eq_ignore_ascii_case("important") => {
+            value = value[..bang].trim_end();
+            true
+        }
+        _ => false,
+    };
+    if value.is_empty() {
+        return Some(Err(StyleError::EmptyValue(name.to_owned())));
+    }
+    Some(Ok(Declaration {
+        // property names are ASCII case-insensitive in CSS
+        name: name.to_ascii_lowercase(),
+        value: value.to_owned(),
+        important,
+    }))
+}
+
+fn is_ident(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Compound of simple selectors, e.g. `rect#floor.wall`.
+#[derive(Debug, Default, PartialEq)]
+struct Selector {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+}
+
+impl Selector {
+    fn parse(selector: &str) -> Option<Self> {
+        let is_delimiter = |c: char| c == '.' || c == '#';
+        let selector = selector.trim();
+        if selector.is_empty() {
+            return None;
+        }
+        let tag_end = selector.find(is_delimiter).unwrap_or(selector.len());
+        let mut parsed = Selector::default();
+        match &selector[..tag_end] {
+            "" | "*" => {}
+            tag if is_ident(tag) => parsed.tag = Some(tag.to_owned()),
+            _ => return None,
+        }
+        let mut rest = &selector[tag_end..];
+        while !rest.is_empty() {
+            let end = rest[1..].find(is_delimiter).map_or(rest.len(), |i| i + 1);
+            let name = &rest[1..end];
+            if !is_ident(name) {
+                return None;
+            }
+            if rest.starts_with('.') {
+                parsed.classes.push(name.to_owned());
+            } else {
+                parsed.id = Some(name.to_owned());
+            }
+            rest = &rest[end..];
+        }
+        Some(parsed)
+    }
+
+    /// (ids, classes, types) as defined in https://www.w3.org/TR/selectors-3/#specificity
+    fn specificity(&self) -> (usize, usize, usize) {
+        (
+            self.id.is_some() as usize,
+            self.classes.len(),
+            self.tag.is_some() as usize,
+        )
+    }
+
+    fn matches(&self, node: &roxmltree::Node) -> bool {
+        let classes: Vec<&str> = node
+            .attribute("class")
+            .map(|c| c.split_whitespace().collect())
+            .unwrap_or_default();
+        self.tag.iter().all(|tag| node.tag_name().name() == tag)
+            && self.id.iter().all(|id| node.attribute("id") == Some(id))
+            && self
+                .classes
+                .iter()
+                .all(|class| classes.contains(&class.as_str()))
+    }
+}
+
+/// (important, origin, specificity): origin is 0 for presentation attributes, 1 for
+/// stylesheets and 2 for the style attribute.
+type Precedence = (bool, u8, (usize, usize, usize));
+
+#[derive(Debug)]
+struct Rule {
+    selectors: Vec<Selector>,
+    declarations: Vec<Declaration>,
+}
+
+/// Rules of the `<style>` elements of a document.
+///
+/// The specified style of each node is memoized by node id, so a stylesheet must only be used
+/// with the nodes of a single document.
+#[derive(Debug, Default)]
+pub struct Stylesheet {
+    rules: Vec<Rule>,
+    specified: RefCell<HashMap<u32, Rc<Specified>>>,
+    displayed: RefCell<HashMap<u32, bool>>,
+}
+
+/// Specified style of a node, and the warnings of its `style` attribute.
+#[derive(Debug, Default)]
+struct Specified {
+    style: HashMap<String, String>,
+    warnings: Vec<StyleError>,
+}
+
+/// Remove `/* comments */` from the CSS.
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+impl Stylesheet {
+    /// Parse the content of a `<style>` element.
+    pub fn parse(css: &str) -> Self {
+        let css = strip_comments(css);
+        let mut rules = Vec::new();
+        let mut rest = css.as_str();
+        while let Some(open) = rest.find('{') {
+            // statements like `@import ...;` end before the prelude of the next rule
+            let prelude = rest[..open].rsplit(';').next().unwrap_or_default().trim();
+            let mut depth = 0;
+            let close = rest[open..]
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .map_or(rest.len(), |(i, _)| open + i);
+            let block = &rest[open + 1..close];
+            rest = rest.get(close + 1..).unwrap_or_default();
+            if prelude.starts_with('@') {
+                continue;
+            }
+            let selectors: Option<Vec<Selector>> =
+                prelude.split(',').map(Selector::parse).collect();
+            if let Some(selectors) = selectors {
+                rules.push(Rule {
+                    selectors,
+                    declarations: parse_declarations(block).0,
+                });
+            }
+        }
+        Stylesheet {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    /// Collect the rules of every `<style>` element of the document.
+    pub fn from_document(doc: &roxmltree::Document) -> Self {
+        let css: String = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("style"))
+            .filter(|n| matches!(n.attribute("type"), None | Some("text/css")))
+            .flat_map(|n| n.children().filter_map(|c| c.text()))
+            .collect();
+        Self::parse(&css)
+    }
+
+    /// The units of the document with the font size of the node, against which `em` and `ex`
+    /// are resolved. Keywords like `medium` keep the default size.
+    pub fn units(&self, node: &roxmltree::Node, units: &Units) -> Units {
+        let mut units = *units;
+        let specified = self.specified(node);
+        if let Some(size) = specified.style.get("font-size") {
+            if let Some(size) = units.parse(size, Axis::Other) {
+                units.font_size = size;
+            }
+        }
+        units
+    }
+
+    /// Whether the node is rendered, that is, neither it nor any of its ancestors has
+    /// `display:none`. Like the style, it is computed once per node, after its parent.
+    pub fn displayed(&self, node: &roxmltree::Node) -> bool {
+        let id = node.id().get();
+        if let Some(&displayed) = self.displayed.borrow().get(&id) {
+            return displayed;
+        }
+        let displayed = self
+            .specified(node)
+            .style
+            .get("display")
+            .map(|d| d.as_str())
+            != Some("none")
+            && node.parent_element().map(|p| self.displayed(&p)) != Some(false);
+        self.displayed.borrow_mut().insert(id, displayed);
+        displayed
+    }
+
+    /// Resolve the declarations that apply to a node, sorted by increasing precedence so that
+    /// the last value of each property wins, with the warnings of its `style` attribute.
+    pub fn cascade(&self, node: &roxmltree::Node) -> (Vec<(String, String)>, Vec<StyleError>) {
+        let mut declarations: Vec<(Precedence, Declaration)> = PRESENTATION_ATTRIBUTES
+            .iter()
+            .filter_map(|&name| {
+                let value = node.attribute(name)?.trim();
+                Some((
+                    (false, 0, (0, 0, 0)),
+                    Declaration {
+                        name: name.to_owned(),
+                        value: value.to_owned(),
+                        important: false,
+                    },
+                ))
+            })
+            .collect();
+        for rule in &self.rules {
+            if let Some(specificity) = rule
+                .selectors
+                .iter()
+                .filter(|s| s.matches(node))
+                .map(Selector::specificity)
+                .max()
+            {
+                declarations.extend(
+                    rule.declarations
+                        .iter()
+                        .map(|d| ((d.important, 1, specificity), d.clone())),
+                );
+            }
+        }
+        let mut warnings = Vec::new();
+        if let Some(style) = node.attribute("style") {
+            let (style, style_warnings) = parse_declarations(style);
+            declarations.extend(style.into_iter().map(|d| ((d.important, 2, (0, 0, 0)), d)));
+            warnings = style_warnings;
+        }
+        // stable, so rules with the same precedence keep the document order
+        declarations.sort_by_key(|(precedence, _)| *precedence);
+        let declarations = declarations
+            .into_iter()
+            .map(|(_, d)| (d.name, d.value))
+            .collect();
+        (declarations, warnings)
+    }
+
+    /// Style of a node after the cascade and the inheritance from its ancestors, with
+    /// `inherit` and `currentColor` resolved, and the warnings of its `style` attribute.
+    pub fn computed(&self, node: &roxmltree::Node) -> (HashMap<String, String>, Vec<StyleError>) {
+        let specified = self.specified(node);
+        let mut style = specified.style.clone();
+        let color = style
+            .get("color")
+            .filter(|c| !c.eq_ignore_ascii_case("currentColor"))
+            .cloned()
+            .unwrap_or_else(|| "#000000".to_owned());
+        for value in style.values_mut() {
+            if value.eq_ignore_ascii_case("currentColor") {
+                *value = color.clone();
+            }
+        }
+        (style, specified.warnings.clone())
+    }
+
+    /// Like `computed`, but `currentColor` is kept as is, so that it is resolved against the
+    /// `color` of the element that uses it and not of the ancestor that set it.
+    ///
+    /// The style of a node is computed once, after the style of its parent, and then reused.
+    fn specified(&self, node: &roxmltree::Node) -> Rc<Specified> {
+        let id = node.id().get();
+        if let Some(style) = self.specified.borrow().get(&id) {
+            return Rc::clone(style);
+        }
+        let parent = node
+            .parent_element()
+            .map(|p| self.specified(&p))
+            .unwrap_or_else(|| {
+                Rc::new(Specified {
+                    style: INHERITED_PROPERTIES
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .collect(),
+                    warnings: Vec::new(),
+                })
+            });
+        let parent = &parent.style;
+        let mut style: HashMap<String, String> = INHERITED_PROPERTIES
+            .iter()
+            .filter_map(|(name, _)| Some((name.to_string(), parent.get(*name)?.clone())))
+            .collect();
+        let (declarations, warnings) = self.cascade(node);
+        for (name, value) in declarations {
+            if value == "inherit" {
+                match parent.get(&name) {
+                    Some(value) => style.insert(name, value.clone()),
+                    None => style.remove(&name),
+                };
+            } else {
+                style.insert(name, value);
+            }
+        }
+        // a relative font size is resolved against the one of the parent, so that the inherited
+        // values are never relative
+        let size = style
+            .get("font-size")
+            .and_then(|s| Length::from_str(s).ok());
+        let scale = size.and_then(|size| match size.unit {
+            LengthUnit::Em => Some(size.num),
+            LengthUnit::Ex => Some(size.num / 2.),
+            LengthUnit::Percent => Some(size.num / 100.),
+            _ => None,
+        });
+        if let Some(scale) = scale {
+            let parent_size = parent
+                .get("font-size")
+                .and_then(|s| Length::from_str(s).ok())
+                .unwrap_or_else(|| Length::new(16., LengthUnit::Px));
+            let size = Length::new(parent_size.num * scale, parent_size.unit);
+            style.insert("font-size".to_owned(), size.to_string());
+        }
+        // opacity is not inherited, but the content of a group is as transparent as the group
+        if let Some(group) = parent.get("opacity").and_then(|o| parse_alpha(o)) {
+            match style.get("opacity").map(|o| parse_alpha(o)) {
+                // an invalid value is kept to be reported
+                Some(None) => {}
+                own => {
+                    let opacity = group * own.flatten().unwrap_or(1.);
+                    style.insert("opacity".to_owned(), opacity.to_string());
+                }
+            }
+        }
+        let specified = Rc::new(Specified { style, warnings });
+        self.specified
+            .borrow_mut()
+            .insert(id, Rc::clone(&specified));
+        specified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cascade(svg: &str) -> Vec<(String, String)> {
+        let doc = roxmltree::Document::parse(svg).unwrap();
+        let node = doc.descendants().find(|n| n.has_tag_name("path")).unwrap();
+        Stylesheet::from_document(&doc).cascade(&node).0
+    }
+
+    fn value<'a>(declarations: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        declarations
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn inheritance() {
+        let doc = roxmltree::Document::parse(
+            r#"<svg><g fill="red" stroke="blue" opacity="0.5" color="green" font-size="8pt">
+                <g stroke="currentColor" color="yellow" style="font-size:150%">
+                    <path id="a" fill="inherit" stroke-width="inherit" d="M0 0"/>
+                    <path id="b" fill="none" color="black" opacity="50%" style="stroke-width" d="M0 0"/>
+                </g>
+            </g></svg>"#,
+        )
+        .unwrap();
+        let stylesheet = Stylesheet::from_document(&doc);
+        let computed = |id: &str| {
+            stylesheet.computed(
+                &doc.descendants()
+                    .find(|n| n.attribute("id") == Some(id))
+                    .unwrap(),
+            )
+        };
+        let (a, warnings) = computed("a");
+        assert!(warnings.is_empty());
+        assert_eq!(a["fill"], "red");
+        assert_eq!(a["stroke"], "yellow");
+        // an inherited property that no ancestor sets takes its initial value
+        assert_eq!(a["stroke-width"], "1");
+        assert_eq!(a["stroke-linejoin"], "miter");
+        assert_eq!(a["opacity"], "0.5");
+        assert_eq!(a["font-size"], "12pt");
+        let (b, warnings) = computed("b");
+        assert_eq!(b["fill"], "none");
+        assert_eq!(b["stroke"], "black");
+        assert_eq!(b["opacity"], "0.25");
+        assert_eq!(
+            warnings,
+            [StyleError::InvalidDeclaration("stroke-width".to_owned())]
+        );
+        // the svg, both groups and both paths, each specified once
+        assert_eq!(stylesheet.specified.borrow().len(), 5);
+    }
+
+    #[test]
+    fn declarations() {
+        let parse = |block: &str| {
+            let (declarations, errors) = parse_declarations(block);
+            let declarations: Vec<(String, String, bool)> = declarations
+                .into_iter()
+                .map(|d| (d.name, d.value, d.important))
+                .collect();
+            (declarations, errors)
+        };
+        let d = |name: &str, value: &str, important| (name.to_owned(), value.to_owned(), important);
+        assert_eq!(parse(""), (vec![], vec![]));
+        assert_eq!(
+            parse(" fill : red ; /* a; b: c */ stroke:blue ! IMPORTANT;;"),
+            (
+                vec![d("fill", "red", false), d("stroke", "blue", true)],
+                vec![]
+            )
+        );
+        assert_eq!(
+            parse("fill:url(data:image/png;base64,iVBO);font-family:'a;b'"),
+            (
+                vec![
+                    d("fill", "url(data:image/png;base64,iVBO)", false),
+                    d("font-family", "'a;b'", false)
+                ],
+                vec![]
+            )
+        );
+        assert_eq!(
+            parse("FILL:red;Stroke-Width:2"),
+            (
+                vec![d("fill", "red", false), d("stroke-width", "2", false)],
+                vec![]
+            )
+        );
+        assert_eq!(
+            parse("fill red;stroke:;fill:blue;font-family:'a"),
+            (
+                vec![d("fill", "blue", false)],
+                vec![
+                    StyleError::InvalidDeclaration("fill red".to_owned()),
+                    StyleError::EmptyValue("stroke".to_owned()),
+                    StyleError::Unterminated("font-family:'a".to_owned())
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn selectors() {
+        assert_eq!(
+            Selector::parse("path#a.b.c").unwrap().specificity(),
+            (1, 2, 1)
+        );
+        assert_eq!(Selector::parse("*").unwrap(), Selector::default());
+        assert!(Selector::parse("g path").is_none());
+        assert!(Selector::parse("a:hover").is_none());
+        assert!(Selector::parse("").is_none());
+    }
+
+    #[test]
+    fn precedence() {
+        let declarations = cascade(
+            r#"<svg><style>
+                /* comment */
+                @media print { path { fill: white } }
+                #p { stroke: blue } .wall { stroke: red; fill: green !important }
+                path { stroke: black; stroke-width: 2 }
+            </style>
+            <path id="p" class="wall floor" fill="red" stroke-linecap="round" style="fill:blue;stroke-width:3" d="M0 0"/></svg>"#,
+        );
+        assert_eq!(value(&declarations, "stroke"), Some("blue"));
+        assert_eq!(value(&declarations, "fill"), Some("green"));
+        assert_eq!(value(&declarations, "stroke-width"), Some("3"));
+        assert_eq!(value(&declarations, "stroke-linecap"), Some("round"));
+    }
+}
@@ -0,0 +1,131 @@
+use lyon::tessellation::TessellationError;
+use std::{error::Error, fmt, io};
+
+/// Everything that can go wrong while loading a SVG map.
+///
+/// The variants that refer to a single element carry its `id` (if any) so that the
+/// culprit can be found in the editor.
+#[derive(Debug)]
+pub enum SvgMapError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file is not a well-formed XML document.
+    Xml {
+        message: String,
+        line: u32,
+        column: u32,
+    },
+    /// The path data of an element could not be parsed.
+    PathSyntax { id: Option<String>, message: String },
+    /// A property of the style of an element has an invalid value.
+    StyleSyntax {
+        id: Option<String>,
+        property: String,
+        value: String,
+    },
+    /// lyon failed to tessellate the fill or the stroke of an element.
+    Tessellation {
+        id: Option<String>,
+        error: TessellationError,
+    },
+    /// The document uses a feature that this crate does not handle.
+    Unsupported(String),
+}
+
+/// A declaration of a style that could not be parsed, see [`SvgStyle::parse`](crate::SvgStyle::parse).
+#[derive(Clone, Debug, PartialEq)]
+pub enum StyleError {
+    /// A declaration without `:` or with an invalid property name, e.g. `fill red`
+    InvalidDeclaration(String),
+    /// A property without a value, e.g. `fill:`
+    EmptyValue(String),
+    /// A declaration with a string, a comment or a parenthesis that is not closed
+    Unterminated(String),
+}
+
+impl fmt::Display for StyleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StyleError::InvalidDeclaration(declaration) => {
+                write!(f, "invalid declaration '{}'", declaration)
+            }
+            StyleError::EmptyValue(property) => write!(f, "missing value for '{}'", property),
+            StyleError::Unterminated(declaration) => write!(
+                f,
+                "unterminated string, comment or parenthesis in '{}'",
+                declaration
+            ),
+        }
+    }
+}
+
+impl Error for StyleError {}
+
+/// Formats the id of an element for error messages.
+struct ElementId<'a>(&'a Option<String>);
+
+impl fmt::Display for ElementId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(id) => write!(f, "element '{}'", id),
+            None => write!(f, "element without id"),
+        }
+    }
+}
+
+impl fmt::Display for SvgMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SvgMapError::Io(e) => write!(f, "could not read the SVG map: {}", e),
+            SvgMapError::Xml {
+                message,
+                line,
+                column,
+            } => write!(f, "invalid XML at {}:{}: {}", line, column, message),
+            SvgMapError::PathSyntax { id, message } => {
+                write!(f, "invalid path data in {}: {}", ElementId(id), message)
+            }
+            SvgMapError::StyleSyntax {
+                id,
+                property,
+                value,
+            } => write!(
+                f,
+                "invalid value '{}' for '{}' in {}",
+                value,
+                property,
+                ElementId(id)
+            ),
+            SvgMapError::Tessellation { id, error } => {
+                write!(f, "could not tessellate {}: {:?}", ElementId(id), error)
+            }
+            SvgMapError::Unsupported(feature) => write!(f, "unsupported SVG feature: {}", feature),
+        }
+    }
+}
+
+impl Error for SvgMapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SvgMapError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SvgMapError {
+    fn from(e: io::Error) -> Self {
+        SvgMapError::Io(e)
+    }
+}
+
+impl From<roxmltree::Error> for SvgMapError {
+    fn from(e: roxmltree::Error) -> Self {
+        let pos = e.pos();
+        SvgMapError::Xml {
+            message: e.to_string(),
+            line: pos.row,
+            column: pos.col,
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! Linear and radial gradients (`<linearGradient>` and `<radialGradient>`), referenced by
+//! `fill` and `stroke` with `url(#id)`.
+use crate::color::{parse_alpha, parse_color};
+use crate::css::Stylesheet;
+use crate::transform::parse_transform;
+use crate::units::{Axis, Units};
+use bevy::prelude::Color;
+use euclid::default::Transform2D;
+use lyon::math::{point, Point, Rect};
+use std::{collections::HashMap, str::FromStr};
+use svgtypes::Length;
+
+const XLINK_NS: &str = "http://www.w3.org/1999/xlink";
+
+/// Geometry of a gradient, in the coordinate system given by its [`GradientUnits`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum GradientKind {
+    /// Offset 0 is at `start` and offset 1 at `end`
+    Linear { start: Point, end: Point },
+    /// Offset 0 is at the `focus` and offset 1 on the circle
+    Radial {
+        center: Point,
+        radius: f32,
+        focus: Point,
+    },
+}
+
+/// The `gradientUnits` of a gradient.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientUnits {
+    /// Fractions of the bounding box of the element
+    ObjectBoundingBox,
+    /// The user space of the element
+    UserSpaceOnUse,
+}
+
+/// The `spreadMethod` of a gradient, how it is painted beyond its ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Spread {
+    Pad,
+    Reflect,
+    Repeat,
+}
+
+/// A `<stop>` of a gradient, with `stop-opacity` applied to its color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: Color,
+}
+
+/// A gradient with the attributes and stops it inherits through `xlink:href` resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub units: GradientUnits,
+    /// The `gradientTransform`
+    pub transform: Transform2D<f32>,
+    pub spread: Spread,
+    /// Stops in increasing offset order
+    pub stops: Vec<GradientStop>,
+}
+
+impl Gradient {
+    /// Color of the gradient at a point of the user space of an element with the given
+    /// bounding box
+    pub fn color_at(&self, p: Point, bbox: &Rect) -> Color {
+        let p = match self.units {
+            GradientUnits::ObjectBoundingBox => point(
+                (p.x - bbox.min_x()) / bbox.width(),
+                (p.y - bbox.min_y()) / bbox.height(),
+            ),
+            GradientUnits::UserSpaceOnUse => p,
+        };
+        let p = self
+            .transform
+            .inverse()
+            .map_or(p, |inverse| inverse.transform_point(p));
+        let t = match self.kind {
+            GradientKind::Linear { start, end } => {
+                let axis = end - start;
+                (p - start).dot(axis) / axis.square_length()
+            }
+            GradientKind::Radial {
+                center,
+                radius,
+                focus,
+            } => {
+                // solve |p - focus - t (center - focus)| = t radius for t
+                let d = center - focus;
+                let e = p - focus;
+                let a = d.square_length() - radius * radius;
+                let ed = e.dot(d);
+                if a.abs() < f32::EPSILON {
+                    e.square_length() / (2. * ed)
+                } else {
+                    (ed - (ed * ed - a * e.square_length()).max(0.).sqrt()) / a
+                }
+            }
+        };
+        // degenerate gradients and bounding boxes are painted with the last stop
+        let t = if t.is_finite() { t } else { 1. };
+        let t = match self.spread {
+            Spread::Pad => t.clamp(0., 1.),
+            Spread::Repeat => t - t.floor(),
+            Spread::Reflect => {
+                let t = t.rem_euclid(2.);
+                if t > 1. {
+                    2. - t
+                } else {
+                    t
+                }
+            }
+        };
+        self.color_at_offset(t)
+    }
+
+    /// Mean color of the stops, where the gradient cannot be drawn
+    pub fn average(&self) -> Color {
+        let n = self.stops.len().max(1) as f32;
+        let sum = self.stops.iter().fold([0.; 4], |mut sum, stop| {
+            for (s, c) in sum.iter_mut().zip(stop.color.as_rgba_f32().iter()) {
+                *s += c;
+            }
+            sum
+        });
+        Color::rgba(sum[0] / n, sum[1] / n, sum[2] / n, sum[3] / n)
+    }
+
+    /// Multiply the alpha of the stops, e.g. by the `fill-opacity`
+    pub(crate) fn with_opacity(mut self, opacity: f32) -> Self {
+        for stop in self.stops.iter_mut() {
+            let a = stop.color.a();
+            stop.color.set_a(a * opacity);
+        }
+        self
+    }
+
+    /// Interpolate the stops, in sRGB as SVG does by default
+    fn color_at_offset(&self, t: f32) -> Color {
+        let next = self.stops.iter().position(|stop| stop.offset > t);
+        match next {
+            None => self.stops.last().map_or(Color::NONE, |stop| stop.color),
+            Some(0) => self.stops[0].color,
+            Some(i) => {
+                let (a, b) = (&self.stops[i - 1], &self.stops[i]);
+                let f = (t - a.offset) / (b.offset - a.offset);
+                let (ca, cb) = (a.color.as_rgba_f32(), b.color.as_rgba_f32());
+                let c: Vec<f32> = ca
+                    .iter()
+                    .zip(cb.iter())
+                    .map(|(x, y)| x + (y - x) * f)
+                    .collect();
+                Color::rgba(c[0], c[1], c[2], c[3])
+            }
+        }
+    }
+}
+
+/// The gradients of a document, by id.
+#[derive(Debug, Default)]
+pub(crate) struct Gradients(HashMap<String, Gradient>);
+
+impl Gradients {
+    pub(crate) fn from_document(
+        doc: &roxmltree::Document,
+        units: &Units,
+        stylesheet: &Stylesheet,
+    ) -> Self {
+        let nodes: HashMap<&str, roxmltree::Node> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("linearGradient") || n.has_tag_name("radialGradient"))
+            .filter_map(|n| n.attribute("id").map(|id| (id, n)))
+            .collect();
+        let gradients = nodes
+            .iter()
+            .map(|(&id, node)| {
+                let chain = href_chain(*node, &nodes);
+                (id.to_owned(), gradient(node, &chain, units, stylesheet))
+            })
+            .collect();
+        Gradients(gradients)
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<&Gradient> {
+        self.0.get(id)
+    }
+}
+
+/// The gradient followed by the ones it references with `xlink:href`, from which it inherits
+/// the attributes and stops it does not have
+fn href_chain<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    nodes: &HashMap<&str, roxmltree::Node<'a, 'input>>,
+) -> Vec<roxmltree::Node<'a, 'input>> {
+    let mut chain = vec![node];
+    while let Some(next) = chain
+        .last()
+        .and_then(|n| {
+            n.attribute((XLINK_NS, "href"))
+                .or_else(|| n.attribute("href"))
+        })
+        .and_then(|href| nodes.get(href.trim_start_matches('#')))
+    {
+        // a reference loop ends the chain
+        if chain.iter().any(|n| n == next) {
+            break;
+        }
+        chain.push(*next);
+    }
+    chain
+}
+
+fn gradient(
+    node: &roxmltree::Node,
+    chain: &[roxmltree::Node],
+    units: &Units,
+    stylesheet: &Stylesheet,
+) -> Gradient {
+    let attribute = |name: &str| chain.iter().find_map(|n| n.attribute(name));
+    let gradient_units = match attribute("gradientUnits") {
+        Some("userSpaceOnUse") => GradientUnits::UserSpaceOnUse,
+        _ => GradientUnits::ObjectBoundingBox,
+    };
+    // bounding box units are fractions, where percentages are relative to 1
+    let length = |name: &str, axis: Axis, default: &str| {
+        let value = attribute(name).unwrap_or(default);
+        match gradient_units {
+            GradientUnits::UserSpaceOnUse => units.parse(value, axis),
+            GradientUnits::ObjectBoundingBox => {
+                Length::from_str(value).ok().map(|l| match l.unit {
+                    svgtypes::LengthUnit::Percent => l.num as f32 / 100.,
+                    _ => l.num as f32,
+                })
+            }
+        }
+        .unwrap_or(0.)
+    };
+    let kind = if node.has_tag_name("linearGradient") {
+        GradientKind::Linear {
+            start: point(length("x1", Axis::X, "0%"), length("y1", Axis::Y, "0%")),
+            end: point(length("x2", Axis::X, "100%"), length("y2", Axis::Y, "0%")),
+        }
+    } else {
+        let center = point(length("cx", Axis::X, "50%"), length("cy", Axis::Y, "50%"));
+        GradientKind::Radial {
+            center,
+            radius: length("r", Axis::Other, "50%"),
+            focus: point(
+                attribute("fx").map_or(center.x, |_| length("fx", Axis::X, "")),
+                attribute("fy").map_or(center.y, |_| length("fy", Axis::Y, "")),
+            ),
+        }
+    };
+    let stops = chain
+        .iter()
+        .map(|n| stops(n, stylesheet))
+        .find(|stops| !stops.is_empty())
+        .unwrap_or_default();
+    Gradient {
+        kind,
+        units: gradient_units,
+        transform: attribute("gradientTransform")
+            .map_or_else(Transform2D::identity, parse_transform),
+        spread: match attribute("spreadMethod") {
+            Some("reflect") => Spread::Reflect,
+            Some("repeat") => Spread::Repeat,
+            _ => Spread::Pad,
+        },
+        stops,
+    }
+}
+
+fn stops(node: &roxmltree::Node, stylesheet: &Stylesheet) -> Vec<GradientStop> {
+    let mut last = 0f32;
+    node.children()
+        .filter(|n| n.has_tag_name("stop"))
+        .map(|n| {
+            let (style, _) = stylesheet.computed(&n);
+            let offset: f32 = n
+                .attribute("offset")
+                .and_then(|o| Length::from_str(o).ok())
+                .map_or(0., |o| match o.unit {
+                    svgtypes::LengthUnit::Percent => o.num as f32 / 100.,
+                    _ => o.num as f32,
+                });
+            // offsets are clamped and cannot decrease
+            last = offset.clamp(0., 1.).max(last);
+            let mut color = style
+                .get("stop-color")
+                .and_then(|c| parse_color(c))
+                .unwrap_or(Color::BLACK);
+            let opacity = style
+                .get("stop-opacity")
+                .and_then(|o| parse_alpha(o))
+                .unwrap_or(1.);
+            color.set_a(color.a() * opacity);
+            GradientStop {
+                offset: last,
+                color,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(svg: &str) -> Gradients {
+        let doc = roxmltree::Document::parse(svg).unwrap();
+        let units = Units::from_root(&doc.root_element(), 96.);
+        Gradients::from_document(&doc, &units, &Stylesheet::from_document(&doc))
+    }
+
+    #[test]
+    fn linear_gradient() {
+        let gradients = parse(
+            r##"<svg xmlns:xlink="http://www.w3.org/1999/xlink"><defs>
+                <linearGradient id="stops">
+                    <stop offset="0" style="stop-color:#ff0000"/>
+                    <stop offset="100%" stop-color="#0000ff" stop-opacity="0.5"/>
+                </linearGradient>
+                <linearGradient id="g" xlink:href="#stops" x1="0" x2="10"
+                    gradientUnits="userSpaceOnUse" spreadMethod="reflect"/>
+            </defs></svg>"##,
+        );
+        let g = gradients.get("g").unwrap();
+        assert_eq!(g.stops.len(), 2);
+        let bbox = Rect::zero();
+        assert_eq!(g.color_at(point(0., 3.), &bbox), Color::RED);
+        assert_eq!(
+            g.color_at(point(10., 0.), &bbox),
+            Color::rgba(0., 0., 1., 0.5)
+        );
+        // reflected back to the start
+        assert_eq!(g.color_at(point(20., 0.), &bbox), Color::RED);
+        let middle = g.color_at(point(5., 0.), &bbox);
+        assert!((middle.r() - 0.5).abs() < 1e-5 && (middle.a() - 0.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn radial_gradient() {
+        let gradients = parse(
+            r##"<svg><radialGradient id="g" gradientTransform="translate(10 0)">
+                <stop offset="0.5" stop-color="#000000"/><stop offset="1" stop-color="#ffffff"/>
+            </radialGradient></svg>"##,
+        );
+        let g = gradients.get("g").unwrap();
+        // the bounding box is 10x10 at the origin, moved 10 to the right by the transform
+        let bbox = Rect::new(point(0., 0.), lyon::math::size(10., 10.));
+        assert_eq!(g.color_at(point(105., 5.), &bbox), Color::BLACK);
+        assert_eq!(g.color_at(point(110., 5.), &bbox), Color::WHITE);
+        let between = g.color_at(point(108.75, 5.), &bbox);
+        assert!((between.r() - 0.5).abs() < 1e-5);
+    }
+}
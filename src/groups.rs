@@ -0,0 +1,144 @@
+//! Groups (`<g>`) of the document, spawned as the parents of their elements.
+use crate::transform::parse_transform;
+use euclid::default::Transform2D;
+use std::collections::HashMap;
+
+const INKSCAPE_NS: &str = "http://www.inkscape.org/namespaces/inkscape";
+
+/// A `<g>` element of the document.
+#[derive(Debug)]
+pub struct GroupSegment {
+    pub id: Option<String>,
+    /// Index of the closest ancestor group
+    pub parent: Option<usize>,
+    /// The `transform` attribute of the group
+    pub transform: Transform2D<f32>,
+    /// Label of the group if it is an Inkscape layer (`inkscape:groupmode="layer"`)
+    pub layer: Option<String>,
+    /// Number of drawable elements before the group in the document
+    pub segments_before: usize,
+}
+
+/// Groups of the document in document order, so parents come before their children.
+#[derive(Debug, Default)]
+pub struct Groups {
+    pub groups: Vec<GroupSegment>,
+    /// Index of the group of each node, by `NodeId`
+    index: HashMap<u32, usize>,
+}
+
+impl Groups {
+    /// The groups for which `include` is true, with their descendants
+    pub fn from_document(
+        doc: &roxmltree::Document,
+        include: impl Fn(&roxmltree::Node) -> bool,
+    ) -> Self {
+        let mut groups = Groups::default();
+        for node in doc
+            .descendants()
+            .filter(|n| n.has_tag_name("g") && include(n))
+        {
+            let parent = groups.closest(&node);
+            groups.index.insert(node.id().get(), groups.groups.len());
+            groups.groups.push(GroupSegment {
+                id: node.attribute("id").map(|s| s.to_owned()),
+                parent,
+                transform: node
+                    .attribute("transform")
+                    .map_or_else(Transform2D::identity, parse_transform),
+                layer: match node.attribute((INKSCAPE_NS, "groupmode")) {
+                    Some("layer") => Some(
+                        node.attribute((INKSCAPE_NS, "label"))
+                            .or_else(|| node.attribute("id"))
+                            .unwrap_or_default()
+                            .to_owned(),
+                    ),
+                    _ => None,
+                },
+                segments_before: 0,
+            });
+        }
+        groups
+    }
+
+    /// Index of the closest group that contains the node
+    pub fn closest(&self, node: &roxmltree::Node) -> Option<usize> {
+        node.ancestors()
+            .skip(1)
+            .find_map(|n| self.index.get(&n.id().get()).copied())
+    }
+
+    /// Set the `segments_before` of the groups, given the `NodeId`s of the drawable elements
+    /// in document order
+    pub fn count_segments(&mut self, segments: &[u32]) {
+        for (&node, &group) in &self.index {
+            self.groups[group].segments_before = segments.partition_point(|&s| s < node);
+        }
+    }
+
+    /// Index of the closest layer that contains the group (or is the group itself)
+    pub fn layer(&self, mut group: Option<usize>) -> Option<usize> {
+        while let Some(g) = group {
+            if self.groups[g].layer.is_some() {
+                return Some(g);
+            }
+            group = self.groups[g].parent;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_tree() {
+        let doc = roxmltree::Document::parse(
+            r#"<svg><g id="a"><path/><g id="b"><path id="p"/></g></g><g id="c"/><path id="q"/></svg>"#,
+        )
+        .unwrap();
+        let groups = Groups::from_document(&doc, |_| true);
+        let parents: Vec<Option<usize>> = groups.groups.iter().map(|g| g.parent).collect();
+        assert_eq!(parents, vec![None, Some(0), None]);
+        let node = |id: &str| {
+            doc.descendants()
+                .find(|n| n.attribute("id") == Some(id))
+                .unwrap()
+        };
+        assert_eq!(groups.closest(&node("p")), Some(1));
+        assert_eq!(groups.closest(&node("q")), None);
+    }
+
+    #[test]
+    fn inkscape_layers() {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+                <g inkscape:groupmode="layer" inkscape:label="Walls"><g><path id="p"/></g></g>
+                <g id="plain"/></svg>"#,
+        )
+        .unwrap();
+        let groups = Groups::from_document(&doc, |_| true);
+        assert_eq!(groups.groups[0].layer.as_deref(), Some("Walls"));
+        assert_eq!(groups.groups[2].layer, None);
+        assert_eq!(groups.layer(Some(1)), Some(0));
+        assert_eq!(groups.layer(Some(2)), None);
+    }
+
+    #[test]
+    fn segments_before_groups() {
+        let doc = roxmltree::Document::parse(
+            r#"<svg><path id="a"/><g><path id="b"/></g><path id="c"/><g/></svg>"#,
+        )
+        .unwrap();
+        let mut groups = Groups::from_document(&doc, |_| true);
+        let paths: Vec<u32> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("path"))
+            .map(|n| n.id().get())
+            .collect();
+        groups.count_segments(&paths);
+        assert_eq!(groups.groups[0].segments_before, 1);
+        assert_eq!(groups.groups[1].segments_before, 3);
+    }
+}
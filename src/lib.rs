@@ -1,120 +1,419 @@
 use bevy::prelude::*;
 use euclid::default::Transform2D;
-use lyon::svg::path_utils::build_path;
-use lyon::tessellation::{FillOptions, StrokeOptions};
-use std::{error::Error, fs};
-use svgtypes::PathParser;
+use lyon::algorithms::aabb;
+use lyon::math::point;
+use std::{
+    borrow::Cow,
+    fs,
+    io::{self, Read},
+};
 
+mod asset;
+mod cache;
+mod color;
+mod css;
+mod error;
+mod gradient;
+mod groups;
 mod lyon_utils;
+mod map_transform;
+mod options;
+mod render;
+mod shapes;
 mod style;
+mod transform;
+mod units;
+pub use asset::{
+    spawn_svg_maps, ElementKey, PartKind, SvgElement, SvgGroup, SvgLayer, SvgMap, SvgMapBatch,
+    SvgMapGroup, SvgMapPart, SvgMapPlugin, SvgMapRoot,
+};
+pub use cache::SvgMapCache;
+pub use error::{StyleError, SvgMapError};
+pub use gradient::{Gradient, GradientKind, GradientStop, GradientUnits, Spread};
+use groups::{GroupSegment, Groups};
+use lyon_utils::Tessellation;
+pub use map_transform::MapTransform;
+pub use options::{HiddenElements, MapOptions, WorldOrigin};
+pub use render::VERTEX_COLOR_PIPELINE_HANDLE;
 use style::StyleSegment;
-pub use style::{StyleStrategy, SvgStyle};
+pub use style::{Paint, StyleStrategy, SvgStyle};
+use units::Units;
 
 /// Return a zero-cost read-only view of the svg XML document as a graph
 fn take_lines_with_style<'a>(
     doc: &'a roxmltree::Document,
-) -> Vec<(&'a str, &'a str, Option<&'a str>, Option<&'a str>)> {
+    units: &Units,
+    stylesheet: &css::Stylesheet,
+) -> Vec<(roxmltree::Node<'a, 'a>, Cow<'a, str>)> {
     doc.descendants()
-        .filter(|n| matches!(n.attribute("d"), Some(_)))
-        .map(|n| {
-            (
-                n.attribute("style").unwrap(),
-                n.attribute("d").unwrap(),
-                n.attribute("id"),
-                n.attribute("class"),
-            )
-        })
+        .filter(shapes::is_rendered)
+        .filter_map(|n| shapes::path_data(&n, &stylesheet.units(&n, units)).map(|d| (n, d)))
         .collect()
 }
 
-/// Parse each "d" node's attribute into a StyleSegment
-fn tokenize_svg(path: &str) -> Result<Vec<StyleSegment>, Box<dyn Error>> {
-    let xmlfile = fs::read_to_string(path)?;
-    let doc = roxmltree::Document::parse(&xmlfile)?;
-    Ok(take_lines_with_style(&doc)
-        .iter()
-        .map(|p| StyleSegment::from(*p))
-        .collect())
+/// Parse the SVG document, checking that its root is a `<svg>` element
+fn parse_svg(xmlfile: &str) -> Result<roxmltree::Document<'_>, SvgMapError> {
+    let doc = roxmltree::Document::parse(xmlfile)?;
+    let root = doc.root_element().tag_name().name();
+    if root != "svg" {
+        return Err(SvgMapError::Unsupported(format!(
+            "root element is <{}> instead of <svg>",
+            root
+        )));
+    }
+    Ok(doc)
 }
 
-fn max_coords(svg_map: &str) -> (f64, f64) {
-    tokenize_svg(svg_map)
-        .unwrap()
-        .iter()
-        .flat_map(|n| PathParser::from(n.traces.as_ref()).map(|n| n.unwrap()))
-        .fold((0f64, 0f64), |acc, n| {
-            let x_f = match n.x() {
-                Some(x) => x.abs().max(acc.0),
-                None => acc.0,
-            };
-            let y_f = match n.y() {
-                Some(y) => y.abs().max(acc.1),
-                None => acc.1,
-            };
-            (x_f, y_f)
-        })
+/// Parse the document once into the mapping to the bevy world, its groups and a StyleSegment
+/// for each "d" node's attribute (or basic shape)
+fn tokenize_svg(
+    svg: &str,
+    options: &MapOptions,
+) -> Result<(MapTransform, Vec<GroupSegment>, Vec<StyleSegment>), SvgMapError> {
+    let doc = parse_svg(svg)?;
+    let root = doc.root_element();
+    let units = Units::from_root(&root, options.dpi);
+    let stylesheet = css::Stylesheet::from_document(&doc);
+    let gradients = gradient::Gradients::from_document(&doc, &units, &stylesheet);
+    let skip_hidden = options.hidden == HiddenElements::Skip;
+    let mut groups = Groups::from_document(&doc, |g| {
+        shapes::is_rendered(g) && (!skip_hidden || stylesheet.displayed(g))
+    });
+    let mut segments = Vec::new();
+    let mut nodes = Vec::new();
+    for (node, d) in take_lines_with_style(&doc, &units, &stylesheet) {
+        let mut segment = StyleSegment::from((&node, d.as_ref(), &units, &stylesheet));
+        if skip_hidden && !segment.style.is_visible() {
+            continue;
+        }
+        segment.style.validate()?;
+        segment.group = groups.closest(&node);
+        segment.style.layer = groups
+            .layer(segment.group)
+            .and_then(|l| groups.groups[l].layer.clone());
+        segment.style.resolve_gradients(&gradients);
+        nodes.push(node.id().get());
+        segments.push(segment);
+    }
+    groups.count_segments(&nodes);
+    Ok((
+        MapTransform::from_root(&root, options),
+        groups.groups,
+        segments,
+    ))
 }
 
 /// For each of the paths in a SVG file, apply a StyleStrategy to translate them into entities with
 /// functionality added to them, dependent of the SVG properties of the path (stroke, fill...)
+///
+/// The entities are spawned as descendants of a [`SvgMapRoot`], mirroring the groups of the
+/// document. The root is returned, so the whole map can be moved or despawned at once.
 pub fn load_svg_map<T: StyleStrategy>(
+    commands: Commands,
+    materials: ResMut<Assets<ColorMaterial>>,
+    meshes: ResMut<Assets<Mesh>>,
+    svg_map: &str,
+    strategy: T,
+) -> Result<Entity, SvgMapError> {
+    load_svg_map_with_options(
+        commands,
+        materials,
+        meshes,
+        svg_map,
+        strategy,
+        &MapOptions::default(),
+    )
+}
+
+/// Same as [`load_svg_map`], placing the map in the world as specified by the [`MapOptions`]
+pub fn load_svg_map_with_options<T: StyleStrategy>(
+    commands: Commands,
+    materials: ResMut<Assets<ColorMaterial>>,
+    meshes: ResMut<Assets<Mesh>>,
+    svg_map: &str,
+    strategy: T,
+    options: &MapOptions,
+) -> Result<Entity, SvgMapError> {
+    let svg = fs::read_to_string(svg_map)?;
+    load_svg_map_from_str(commands, materials, meshes, &svg, strategy, options)
+}
+
+/// Same as [`load_svg_map_with_options`], reading the map from its source bytes, which must be
+/// UTF-8 encoded
+pub fn load_svg_map_from_bytes<T: StyleStrategy>(
+    commands: Commands,
+    materials: ResMut<Assets<ColorMaterial>>,
+    meshes: ResMut<Assets<Mesh>>,
+    svg: &[u8],
+    strategy: T,
+    options: &MapOptions,
+) -> Result<Entity, SvgMapError> {
+    let svg =
+        std::str::from_utf8(svg).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    load_svg_map_from_str(commands, materials, meshes, svg, strategy, options)
+}
+
+/// Same as [`load_svg_map_with_options`], reading the map from any reader (a downloaded file,
+/// a socket...)
+pub fn load_svg_map_from_reader<T: StyleStrategy, R: Read>(
+    commands: Commands,
+    materials: ResMut<Assets<ColorMaterial>>,
+    meshes: ResMut<Assets<Mesh>>,
+    mut reader: R,
+    strategy: T,
+    options: &MapOptions,
+) -> Result<Entity, SvgMapError> {
+    let mut svg = String::new();
+    reader.read_to_string(&mut svg)?;
+    load_svg_map_from_str(commands, materials, meshes, &svg, strategy, options)
+}
+
+/// Same as [`load_svg_map_with_options`], taking the source of the SVG document instead of its
+/// path, e.g. a map embedded with `include_str!` or generated at runtime
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_svg_map::{load_svg_map_from_str, MapOptions, StyleStrategy};
+///
+/// struct MyStrategy;
+/// impl StyleStrategy for MyStrategy {}
+///
+/// fn setup(com: Commands, mat: ResMut<Assets<ColorMaterial>>, mesh: ResMut<Assets<Mesh>>) {
+///     let svg = include_str!("../assets/ex.svg");
+///     load_svg_map_from_str(com, mat, mesh, svg, MyStrategy, &MapOptions::default()).unwrap();
+/// }
+/// ```
+pub fn load_svg_map_from_str<T: StyleStrategy>(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
-    svg_map: &str,
+    svg: &str,
     strategy: T,
-) {
-    let (x_max, y_max) = max_coords(svg_map);
-    let (x_max, y_max) = (x_max as f32, y_max as f32);
+    options: &MapOptions,
+) -> Result<Entity, SvgMapError> {
+    let map = SvgMap::parse(svg, options)?;
+    spawn_svg_map(&mut commands, &mut materials, &mut meshes, &map, &strategy)
+}
 
-    for StyleSegment { style, traces } in tokenize_svg(svg_map).unwrap().iter() {
-        let color_handle = materials.add(strategy.color_decider(style).into());
-        // TODO: this transformation are a joke...
-        let builder = lyon::path::Path::builder().with_svg().transformed(
-            Transform2D::translation(x_max + x_max / 2f32, y_max / 2f32) // translate to bevy coordinates
-                .pre_rotate(euclid::Angle::radians(std::f32::consts::PI / 2.)) // rotate 180º for some reason
-                .then(&Transform2D::new(0f32, 1f32, 1f32, 0f32, 0f32, 0f32)) // mirror for some reason
-                .then_translate(euclid::Vector2D::new(0., -y_max)), // translate again to bevy coordinates
-        );
-        let path = build_path(builder, traces).unwrap();
-        if matches!(style.stroke(), Some(_)) {
-            strategy.component_decider(
-                &style,
-                commands.spawn().insert_bundle(lyon_utils::stroke(
-                    path.clone(),
-                    color_handle.clone(),
-                    &mut meshes,
-                    Vec3::new(-x_max, -y_max, 0.0),
-                    &StrokeOptions::default()
-                        .with_line_width(strategy.width_decider(style))
-                        .with_line_cap(strategy.linecap_decider(style))
-                        .with_line_join(strategy.linejoin_decider(style)),
-                )),
-            )
-        }
-        if matches!(style.fill(), Some(_)) {
-            strategy.component_decider(
-                &style,
-                commands.spawn().insert_bundle(lyon_utils::fill(
-                    path,
-                    color_handle,
-                    &mut meshes,
-                    Vec3::new(-x_max, -y_max, 0.0),
-                    &FillOptions::default(),
-                )),
-            )
+/// Spawn the elements of a [`SvgMap`] (e.g. loaded through the [`SvgMapPlugin`]), applying the
+/// StyleStrategy to each of them
+///
+/// Returns the [`SvgMapRoot`] entity, whose descendants mirror the groups of the document.
+pub fn spawn_svg_map<T: StyleStrategy>(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    meshes: &mut Assets<Mesh>,
+    map: &SvgMap,
+    strategy: &T,
+) -> Result<Entity, SvgMapError> {
+    spawn_svg_map_with_cache(
+        commands,
+        materials,
+        meshes,
+        map,
+        strategy,
+        &mut SvgMapCache::default(),
+    )
+}
+
+/// Same as [`spawn_svg_map`], sharing the materials and meshes in the [`SvgMapCache`] with
+/// other maps
+pub fn spawn_svg_map_with_cache<T: StyleStrategy>(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    meshes: &mut Assets<Mesh>,
+    map: &SvgMap,
+    strategy: &T,
+    cache: &mut SvgMapCache,
+) -> Result<Entity, SvgMapError> {
+    // everything is built before spawning so that an error does not leave half a map behind
+    let bundles = build_bundles(materials, meshes, cache, map, strategy)?;
+    let root = commands
+        .spawn()
+        .insert_bundle((SvgMapRoot, Transform::default(), GlobalTransform::default()))
+        .id();
+    asset::sync_map(
+        commands,
+        root,
+        map,
+        bundles,
+        strategy,
+        asset::Spawned::default(),
+    );
+    Ok(root)
+}
+
+/// Tessellate the elements of the map into the bundles that would be spawned for them
+fn build_bundles<'a, T: StyleStrategy>(
+    materials: &mut Assets<ColorMaterial>,
+    meshes: &mut Assets<Mesh>,
+    cache: &mut SvgMapCache,
+    map: &'a SvgMap,
+    strategy: &T,
+) -> Result<asset::Bundles<'a>, SvgMapError> {
+    // transform of each group relative to the root, to place the batched parts
+    let mut frames: Vec<Mat4> = Vec::with_capacity(map.groups().len());
+    for group in map.groups() {
+        let parent = group.parent().map_or(Mat4::IDENTITY, |p| frames[p]);
+        frames.push(parent * group.transform().compute_matrix());
+    }
+    let mut parts = Vec::new();
+    let mut batches: Vec<(Handle<ColorMaterial>, bool, lyon_utils::Geometry)> = Vec::new();
+    for element in map.elements() {
+        let style = element.style();
+        let tessellation_error = |error| SvgMapError::Tessellation {
+            id: style.id().clone(),
+            error,
+        };
+        // the outline is moved to the origin so that copies of it share the mesh
+        let (offset, path) = lyon_utils::rebase(element.path());
+        // the z of the parts is relative to the entity of their parent
+        let parent_z = element.parent().map_or(0., |g| map.groups()[g].z);
+        // gradients are defined in the user space of the element, around its bounding box
+        let to_user = element
+            .transform
+            .inverse()
+            .unwrap_or_else(Transform2D::identity);
+        let bbox = aabb::bounding_rect(element.path().clone().transformed(&to_user).iter());
+        let scale = transform::mean_scale(&element.transform);
+        let bounds = aabb::bounding_rect(element.path().iter());
+        let stroke_options = {
+            let options = strategy.stroke_options_decider(style, &bounds);
+            options.with_line_width(options.line_width * scale)
+        };
+        let dashed = strategy.dasharray_decider(style).map(|lengths| {
+            let lengths: Vec<f32> = lengths.iter().map(|l| l * scale).collect();
+            let offset = strategy.dashoffset_decider(style) * scale;
+            lyon_utils::dash(&path, &lengths, offset, stroke_options.tolerance)
+        });
+        for &(kind, z) in element.parts() {
+            let translation = Vec3::new(
+                offset.x,
+                offset.y,
+                strategy.z_decider(style, kind, z) - parent_z,
+            );
+            let (paint, tessellation) = match kind {
+                PartKind::Stroke => (
+                    strategy.paint_decider(style),
+                    Tessellation::Stroke(stroke_options),
+                ),
+                PartKind::Fill => (
+                    strategy.paint_fill_decider(style),
+                    Tessellation::Fill(strategy.fill_options_decider(style, &bounds)),
+                ),
+            };
+            let outline = match (kind, &dashed) {
+                (PartKind::Stroke, Some(dashed)) => dashed,
+                _ => &path,
+            };
+            let (color, gradient) = match &paint {
+                Paint::Gradient(gradient) => (Color::WHITE, Some(gradient)),
+                paint => (paint.color().unwrap_or(Color::NONE), None),
+            };
+            // with vertex colors, the color goes to the mesh and the material is shared
+            let vertex_colors = gradient.is_some() || strategy.vertex_color_decider(style);
+            let material =
+                cache.material(materials, if vertex_colors { Color::WHITE } else { color });
+            let tessellate = || {
+                let geometry = tessellation.tessellate(outline)?;
+                Ok(match gradient {
+                    Some(gradient) => {
+                        let color_at = |[x, y, _]: [f32; 3]| {
+                            let p = to_user.transform_point(point(x + offset.x, y + offset.y));
+                            gradient.color_at(p, &bbox).as_rgba_f32()
+                        };
+                        // lyon only puts vertices on the outline, so the inside is subdivided
+                        // until the colors between the vertices are close to the interpolated
+                        // ones, e.g. around the center of a radial gradient or at a middle stop
+                        let extent = bounds.width().max(bounds.height());
+                        geometry
+                            .subdivide(|a, b| {
+                                let length = (a[0] - b[0]).hypot(a[1] - b[1]);
+                                let middle = [(a[0] + b[0]) / 2., (a[1] + b[1]) / 2., a[2]];
+                                let (ca, cb, cm) = (color_at(a), color_at(b), color_at(middle));
+                                let error = (0..4)
+                                    .map(|i| (cm[i] - (ca[i] + cb[i]) / 2.).abs())
+                                    .fold(0., f32::max);
+                                length > extent / 8. || (length > extent / 128. && error > 1. / 64.)
+                            })
+                            .with_colors(|v| {
+                                let [r, g, b, a] = color_at(v);
+                                Color::rgba(r, g, b, a)
+                            })
+                    }
+                    None if vertex_colors => geometry.with_color(color),
+                    None => geometry,
+                })
+            };
+            // a hidden part keeps its own mesh, so that it can be shown later
+            if style.is_visible() && strategy.batch_decider(style) {
+                let geometry = tessellate().map_err(tessellation_error)?;
+                let frame = element.parent().map_or(Mat4::IDENTITY, |g| frames[g]);
+                let batch = batches
+                    .iter()
+                    .position(|(m, v, _)| *m == material && *v == vertex_colors);
+                let batch = match batch {
+                    Some(batch) => batch,
+                    None => {
+                        batches.push((material, vertex_colors, Default::default()));
+                        batches.len() - 1
+                    }
+                };
+                batches[batch]
+                    .2
+                    .append(&geometry, &(frame * Mat4::from_translation(translation)));
+                let transform = Transform::from_translation(translation);
+                parts.push((element, kind, asset::PartBundle::Batched(transform)));
+            } else {
+                let mut geometry = Vec::new();
+                lyon_utils::path_key(outline, &mut geometry);
+                tessellation.key(&mut geometry);
+                if vertex_colors {
+                    geometry.extend(color.as_rgba_f32().iter().map(|c| c.to_bits()));
+                }
+                // the colors of a gradient depend on where the element is, so it is not shared
+                let mesh = match gradient {
+                    Some(_) => tessellate().map(|g| meshes.add(g.into())),
+                    None => cache.mesh(meshes, geometry, || tessellate().map(Mesh::from)),
+                }
+                .map_err(tessellation_error)?;
+                let mut bundle = lyon_utils::sprite(material, mesh, translation);
+                if vertex_colors {
+                    render::use_vertex_colors(&mut bundle);
+                }
+                bundle.visible.is_visible = style.is_visible();
+                parts.push((element, kind, asset::PartBundle::Sprite(Box::new(bundle))));
+            }
         }
     }
+    Ok(asset::Bundles {
+        parts,
+        batches: batches
+            .into_iter()
+            .map(|(material, vertex_colors, geometry)| {
+                let mut bundle =
+                    lyon_utils::sprite(material, meshes.add(geometry.into()), Vec3::ZERO);
+                if vertex_colors {
+                    render::use_vertex_colors(&mut bundle);
+                }
+                bundle
+            })
+            .collect(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use svgtypes::PathSegment;
+    use svgtypes::{PathParser, PathSegment};
+
+    fn tokenize_file(path: &str) -> Vec<StyleSegment> {
+        let svg = fs::read_to_string(path).unwrap();
+        tokenize_svg(&svg, &MapOptions::default()).unwrap().2
+    }
     #[test]
     fn tokenize_properly() {
-        let (_, _) = tokenize_svg("assets/ex.svg")
-            .unwrap()
+        let (_, _) = tokenize_file("assets/ex.svg")
             .iter()
             .flat_map(|n| PathParser::from(n.traces.as_ref()).map(|n| n.unwrap()))
             .fold((0f64, 0f64), |acc, n| match n {
@@ -129,9 +428,195 @@ mod tests {
     }
     #[test]
     fn tokenize_id_attribute() {
-        assert!(tokenize_svg("assets/ex.svg")
-            .unwrap()
+        assert!(tokenize_file("assets/ex.svg")
             .iter()
             .any(|st| st.style.id().is_some()));
     }
+    #[test]
+    fn tokenize_basic_shapes() {
+        assert!(tokenize_file("assets/with_shapes.svg")
+            .iter()
+            .any(|st| st.style.id().as_deref() == Some("rect830")));
+        // without any style, a shape is filled in black and not stroked
+        let segments = tokenize_svg(
+            r#"<svg><rect width="5" height="5"/></svg>"#,
+            &MapOptions::default(),
+        )
+        .unwrap()
+        .2;
+        assert_eq!(segments[0].style.fill(), Some(Color::BLACK));
+        assert_eq!(segments[0].style.stroke(), None);
+    }
+    #[test]
+    fn tokenize_stylesheet() {
+        let segments = tokenize_file("assets/with_css.svg");
+        let style = |id: &str| {
+            &segments
+                .iter()
+                .find(|st| st.style.id().as_deref() == Some(id))
+                .unwrap()
+                .style
+        };
+        assert_eq!(style("wall1").stroke(), Some(Color::RED));
+        assert_eq!(style("wall1").stroke_width(), Some(2.));
+        assert_eq!(style("floor").fill(), Some(Color::GREEN));
+        assert_eq!(style("floor").stroke(), Some(Color::BLACK));
+
+        let segments = tokenize_svg(
+            r#"<svg><path d="M 0 0 H 1" style="stroke:red;fill red"/></svg>"#,
+            &MapOptions::default(),
+        )
+        .unwrap()
+        .2;
+        assert_eq!(segments[0].style.stroke(), Some(Color::RED));
+        assert_eq!(
+            segments[0].style.warnings(),
+            [StyleError::InvalidDeclaration("fill red".to_owned())]
+        );
+    }
+    #[test]
+    fn tokenize_font_size() {
+        let segments = tokenize_svg(
+            r#"<svg><g font-size="10"><path d="M 0 0 H 1" stroke-width="0.1em"/>
+                <g style="font-size:200%"><rect width="1em" height="1ex" stroke-width="1ex"/>
+                </g></g></svg>"#,
+            &MapOptions::default(),
+        )
+        .unwrap()
+        .2;
+        assert_eq!(segments[0].style.stroke_width(), Some(1.));
+        assert_eq!(segments[1].style.stroke_width(), Some(10.));
+        assert_eq!(segments[1].traces, "M 0 0 H 20 V 10 H 0 Z");
+    }
+    #[test]
+    fn tokenize_gradients() {
+        let segments = tokenize_file("assets/with_gradients.svg");
+        let fill = |id: &str| {
+            segments
+                .iter()
+                .find(|st| st.style.id().as_deref() == Some(id))
+                .and_then(|st| st.style.fill_paint())
+        };
+        match fill("floor") {
+            Some(Paint::Gradient(gradient)) => {
+                assert_eq!(gradient.units, GradientUnits::UserSpaceOnUse);
+                assert_eq!(gradient.stops.len(), 2);
+            }
+            paint => panic!("unexpected fill {:?}", paint),
+        }
+        match fill("pond") {
+            Some(Paint::Gradient(gradient)) => {
+                assert_eq!(gradient.spread, Spread::Reflect);
+                assert!(gradient.stops.iter().all(|s| s.color.a() == 0.5));
+            }
+            paint => panic!("unexpected fill {:?}", paint),
+        }
+    }
+    #[test]
+    fn definitions_are_not_drawn() {
+        let svg = r#"<svg><defs>
+                <clipPath id="clip"><rect style="fill:#ff0000" width="1" height="1"/></clipPath>
+                <g id="prop"><path d="M 0 0 H 1" stroke="red"/></g></defs>
+            <mask><circle r="1" fill="white"/></mask>
+            <symbol><g><rect width="1" height="1" fill="red"/></g></symbol>
+            <marker><path d="M 0 0 L 1 1" stroke="red"/></marker>
+            <pattern><rect width="1" height="1" fill="red"/></pattern>
+            <g><rect id="floor" width="1" height="1" fill="red"/></g></svg>"#;
+        let (_, groups, segments) = tokenize_svg(svg, &MapOptions::default()).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].style.id().as_deref(), Some("floor"));
+    }
+    #[test]
+    fn hidden_elements() {
+        let svg = r#"<svg xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+            <g inkscape:groupmode="layer" style="display:none"><path id="notes" d="M 0 0 H 1"/></g>
+            <g visibility="hidden">
+                <path id="helper" d="M 0 0 H 1"/>
+                <path id="shown" visibility="visible" d="M 0 0 H 1"/>
+            </g></svg>"#;
+        let visible = |hidden| {
+            let options = MapOptions {
+                hidden,
+                ..Default::default()
+            };
+            let (_, groups, segments) = tokenize_svg(svg, &options).unwrap();
+            let segments: Vec<(String, bool)> = segments
+                .iter()
+                .map(|s| (s.style.id().clone().unwrap(), s.style.is_visible()))
+                .collect();
+            (groups.len(), segments)
+        };
+        assert_eq!(
+            visible(HiddenElements::Skip),
+            (1, vec![("shown".to_owned(), true)])
+        );
+        assert_eq!(
+            visible(HiddenElements::Invisible),
+            (
+                2,
+                vec![
+                    ("notes".to_owned(), false),
+                    ("helper".to_owned(), false),
+                    ("shown".to_owned(), true)
+                ]
+            )
+        );
+        // only the elements that are kept are validated
+        let svg = r#"<svg><path d="M 0 0 H 1" display="none" stroke-width="x"/></svg>"#;
+        assert!(tokenize_svg(svg, &MapOptions::default()).is_ok());
+        let options = MapOptions {
+            hidden: HiddenElements::Invisible,
+            ..Default::default()
+        };
+        assert!(matches!(
+            tokenize_svg(svg, &options),
+            Err(SvgMapError::StyleSyntax { .. })
+        ));
+    }
+    #[test]
+    fn errors_are_reported() {
+        struct Strategy;
+        impl StyleStrategy for Strategy {}
+        type Loaded = Option<Result<Entity, SvgMapError>>;
+        fn load_missing(
+            commands: Commands,
+            materials: ResMut<Assets<ColorMaterial>>,
+            meshes: ResMut<Assets<Mesh>>,
+            mut loaded: ResMut<Loaded>,
+        ) {
+            let result = load_svg_map(commands, materials, meshes, "assets/missing.svg", Strategy);
+            *loaded = Some(result);
+        }
+        let mut builder = App::build();
+        builder
+            .add_plugin(bevy::core::CorePlugin)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_asset::<Mesh>()
+            .add_asset::<ColorMaterial>()
+            .insert_resource::<Loaded>(None)
+            .add_system(load_missing.system());
+        let mut app = builder.app;
+        app.update();
+        assert!(matches!(
+            app.world.get_resource::<Loaded>().unwrap(),
+            Some(Err(SvgMapError::Io(_)))
+        ));
+        assert!(matches!(
+            tokenize_svg(
+                "<svg><path d='M 0 0 L 1' stroke-width='x'/></svg>",
+                &MapOptions::default()
+            ),
+            Err(SvgMapError::StyleSyntax { .. })
+        ));
+        assert!(matches!(
+            parse_svg("<svg>\n<path d=''></svg>"),
+            Err(SvgMapError::Xml { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_svg("<html/>"),
+            Err(SvgMapError::Unsupported(_))
+        ));
+        assert_ne!(asset::path_error("M 10 10 L 20 x"), "malformed path");
+    }
 }
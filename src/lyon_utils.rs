@@ -1,49 +1,144 @@
 //! Mainly taken from bevy_input_prototype
 use bevy::{prelude::*, render::mesh::Indices};
+use lyon::math::{Point, Translation, Vector};
+use lyon::path::{iterator::PathIterator, Path, PathEvent};
 use lyon::tessellation::{
     BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
-    StrokeVertex, VertexBuffers,
+    StrokeVertex, TessellationError, VertexBuffers,
 };
+use std::collections::HashMap;
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct ParseError;
+#[derive(Default)]
+pub struct Geometry {
+    pub buffers: VertexBuffers<[f32; 3], u32>,
+    /// Linear color of each vertex, if the mesh has vertex colors
+    pub colors: Option<Vec<[f32; 4]>>,
+}
+
+impl Geometry {
+    /// Paint all the vertices with the color
+    pub fn with_color(self, color: Color) -> Self {
+        self.with_colors(|_| color)
+    }
+
+    /// Paint each vertex with the color given for its position, e.g. by a gradient
+    pub fn with_colors(mut self, color_at: impl Fn([f32; 3]) -> Color) -> Self {
+        self.colors = Some(
+            self.buffers
+                .vertices
+                .iter()
+                .map(|&v| color_at(v).as_linear_rgba_f32())
+                .collect(),
+        );
+        self
+    }
 
-struct Geometry(pub VertexBuffers<[f32; 3], u32>);
+    /// Bisect the triangles that have an edge for which `split` is true, until none is left, so
+    /// that the vertices are dense enough to follow e.g. a gradient. Like in Rivara's
+    /// refinement, a triangle is split at its longest edge and the neighbours of a split edge
+    /// are split too, so the triangles do not get thin and the mesh has no cracks.
+    pub fn subdivide(mut self, split: impl Fn([f32; 3], [f32; 3]) -> bool) -> Self {
+        // an upper bound for degenerate inputs, far above what a gradient needs
+        const MAX_VERTICES: usize = 1 << 16;
+        let key = |a: u32, b: u32| (a.min(b), a.max(b));
+        let edges = |t: &[u32; 3]| [key(t[0], t[1]), key(t[1], t[2]), key(t[2], t[0])];
+        let vertices = &mut self.buffers.vertices;
+        // the vertex added in the middle of each split edge, shared by both its triangles
+        let mut middles: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut triangles: Vec<[u32; 3]> = self
+            .buffers
+            .indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        let mut done: Vec<[u32; 3]> = Vec::with_capacity(triangles.len());
+        while !triangles.is_empty() {
+            while let Some(t) = triangles.pop() {
+                let refine = edges(&t).iter().any(|&(a, b)| {
+                    middles.contains_key(&(a, b))
+                        || (vertices.len() < MAX_VERTICES
+                            && split(vertices[a as usize], vertices[b as usize]))
+                });
+                if !refine {
+                    done.push(t);
+                    continue;
+                }
+                let length = |e: usize| {
+                    let (a, b) = (vertices[t[e] as usize], vertices[t[(e + 1) % 3] as usize]);
+                    (a[0] - b[0]).hypot(a[1] - b[1])
+                };
+                let e = (0..3)
+                    .max_by(|&x, &y| length(x).total_cmp(&length(y)))
+                    .unwrap_or(0);
+                let (a, b, c) = (t[e], t[(e + 1) % 3], t[(e + 2) % 3]);
+                let middle = *middles.entry(key(a, b)).or_insert_with(|| {
+                    let (p, q) = (vertices[a as usize], vertices[b as usize]);
+                    vertices.push([(p[0] + q[0]) / 2., (p[1] + q[1]) / 2., (p[2] + q[2]) / 2.]);
+                    vertices.len() as u32 - 1
+                });
+                triangles.push([a, middle, c]);
+                triangles.push([middle, b, c]);
+            }
+            // a triangle can be done before one of its edges is split by its neighbour
+            let (hanging, conforming) = done
+                .into_iter()
+                .partition(|t| edges(t).iter().any(|e| middles.contains_key(e)));
+            triangles = hanging;
+            done = conforming;
+        }
+        self.buffers.indices = done.concat();
+        self
+    }
+
+    /// Merge the geometry of `other`, moved by `transform`, into this one
+    pub fn append(&mut self, other: &Geometry, transform: &Mat4) {
+        let base = self.buffers.vertices.len() as u32;
+        self.buffers.vertices.extend(
+            other
+                .buffers
+                .vertices
+                .iter()
+                .map(|&v| <[f32; 3]>::from(transform.transform_point3(Vec3::from(v)))),
+        );
+        self.buffers
+            .indices
+            .extend(other.buffers.indices.iter().map(|i| i + base));
+        if let Some(colors) = &other.colors {
+            self.colors
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(colors);
+        }
+    }
+}
 
 impl From<Geometry> for Mesh {
     fn from(geometry: Geometry) -> Self {
-        let num_vertices = geometry.0.vertices.len();
+        let num_vertices = geometry.buffers.vertices.len();
         let mut mesh = Self::new(bevy::render::pipeline::PrimitiveTopology::TriangleList);
-        mesh.set_indices(Some(Indices::U32(geometry.0.indices)));
-        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, geometry.0.vertices);
-
-        let mut normals: Vec<[f32; 3]> = Vec::new();
-        let mut uvs: Vec<[f32; 2]> = Vec::new();
-        for _ in 0..num_vertices {
-            normals.push([0.0, 0.0, 0.0]);
-            uvs.push([0.0, 0.0]);
+        mesh.set_indices(Some(Indices::U32(geometry.buffers.indices)));
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, geometry.buffers.vertices);
+        // the shapes are flat, facing the camera
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; num_vertices]);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; num_vertices]);
+        if let Some(colors) = geometry.colors {
+            mesh.set_attribute(Mesh::ATTRIBUTE_COLOR, colors);
         }
 
-        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-
         mesh
     }
 }
 
-/// Returns a `SpriteComponents` bundle with the given [`Geometry`](Geometry)
-/// and `ColorMaterial`.
+/// Returns a `SpriteComponents` bundle with the given mesh and `ColorMaterial`.
 ///
 /// adapted from [bevy_prototype_lyon](https://github.com/Nilirad/bevy_prototype_lyon/blob/master/src/path.rs)
-fn create_sprite(
+pub fn sprite(
     material: Handle<ColorMaterial>,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    geometry: Geometry,
+    mesh: Handle<Mesh>,
     translation: Vec3,
 ) -> SpriteBundle {
     SpriteBundle {
         material,
-        mesh: meshes.add(geometry.into()),
+        mesh,
         sprite: Sprite {
             size: Vec2::new(1.0, 1.0),
             ..Default::default()
@@ -53,52 +148,330 @@ fn create_sprite(
     }
 }
 
-/// Stroke to bevy components.
+/// Translate the path so that it begins at the origin, so that copies of an outline in
+/// different places have the same geometry. Returns the translation that puts it back.
+pub fn rebase(path: &Path) -> (Vector, Path) {
+    match path.iter().next() {
+        Some(PathEvent::Begin { at }) => (
+            at.to_vector(),
+            path.clone().transformed(&Translation::new(-at.x, -at.y)),
+        ),
+        _ => (Vector::zero(), path.clone()),
+    }
+}
+
+/// Cut the path into the dashes of `stroke-dasharray`, starting `offset` into the pattern at
+/// the beginning of each subpath. An odd number of lengths is repeated to make the pattern;
+/// negative lengths or a pattern of length zero leave the path solid. Curves are flattened
+/// with the `tolerance` of the tessellation of the stroke.
+pub fn dash(path: &Path, lengths: &[f32], offset: f32, tolerance: f32) -> Path {
+    let mut pattern = lengths.to_vec();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(lengths);
+    }
+    let total: f32 = pattern.iter().sum();
+    if pattern.iter().any(|l| *l < 0. || !l.is_finite()) || total <= 0. || !offset.is_finite() {
+        return path.clone();
+    }
+    let mut dasher = Dasher {
+        builder: Path::builder(),
+        offset: offset.rem_euclid(total),
+        pattern,
+        index: 0,
+        left: 0.,
+    };
+    for event in path.iter().flattened(tolerance) {
+        match event {
+            PathEvent::Begin { at } => dasher.begin(at),
+            PathEvent::Line { from, to } => dasher.line(from, to),
+            PathEvent::End { last, first, close } => {
+                if close {
+                    dasher.line(last, first);
+                }
+                if dasher.on() {
+                    dasher.builder.end(false);
+                }
+            }
+            // the flattened path only has lines
+            _ => {}
+        }
+    }
+    dasher.builder.build()
+}
+
+/// State of the walk along a path being dashed
+struct Dasher {
+    builder: lyon::path::path::Builder,
+    pattern: Vec<f32>,
+    offset: f32,
+    /// The current dash (even) or gap (odd) of the pattern and the length left of it
+    index: usize,
+    left: f32,
+}
+
+impl Dasher {
+    fn on(&self) -> bool {
+        self.index & 1 == 0
+    }
+
+    /// Start a subpath at the offset of the pattern
+    fn begin(&mut self, at: Point) {
+        let mut phase = self.offset;
+        self.index = 0;
+        while phase >= self.pattern[self.index] && self.index < self.pattern.len() - 1 {
+            phase -= self.pattern[self.index];
+            self.index += 1;
+        }
+        self.left = self.pattern[self.index] - phase;
+        if self.on() {
+            self.builder.begin(at);
+        }
+    }
+
+    fn line(&mut self, mut from: Point, to: Point) {
+        let mut length = (to - from).length();
+        while length > self.left {
+            let p = from.lerp(to, self.left / length);
+            if self.on() {
+                self.builder.line_to(p);
+                self.builder.end(false);
+            } else {
+                self.builder.begin(p);
+            }
+            length -= self.left;
+            from = p;
+            self.index = (self.index + 1) % self.pattern.len();
+            self.left = self.pattern[self.index];
+        }
+        self.left -= length;
+        if self.on() {
+            self.builder.line_to(to);
+        }
+    }
+}
+
+/// Append the geometry of the path, as bits, to the key of a mesh
+pub fn path_key(path: &Path, key: &mut Vec<u32>) {
+    let zero = Point::zero();
+    for event in path.iter() {
+        let (verb, points) = match event {
+            PathEvent::Begin { at } => (0u32, [at, zero, zero]),
+            PathEvent::Line { to, .. } => (1, [to, zero, zero]),
+            PathEvent::Quadratic { ctrl, to, .. } => (2, [ctrl, to, zero]),
+            PathEvent::Cubic {
+                ctrl1, ctrl2, to, ..
+            } => (3, [ctrl1, ctrl2, to]),
+            PathEvent::End { close, .. } => (4 + close as u32, [zero; 3]),
+        };
+        key.push(verb);
+        for p in points.iter() {
+            key.extend_from_slice(&[p.x.to_bits(), p.y.to_bits()]);
+        }
+    }
+}
+
+/// How a part of an element is tessellated.
+pub enum Tessellation {
+    Stroke(StrokeOptions),
+    Fill(FillOptions),
+}
+
+impl Tessellation {
+    pub fn tessellate(&self, path: &Path) -> Result<Geometry, TessellationError> {
+        match self {
+            Tessellation::Stroke(options) => stroke(path, options),
+            Tessellation::Fill(options) => fill(path, options),
+        }
+    }
+
+    /// Append the options that change the geometry to the key of a mesh
+    pub fn key(&self, key: &mut Vec<u32>) {
+        match self {
+            Tessellation::Stroke(options) => key.extend_from_slice(&[
+                0,
+                options.line_width.to_bits(),
+                options.start_cap as u32,
+                options.end_cap as u32,
+                options.line_join as u32,
+                options.miter_limit.to_bits(),
+                options.tolerance.to_bits(),
+            ]),
+            Tessellation::Fill(options) => {
+                key.extend_from_slice(&[1, options.fill_rule as u32, options.tolerance.to_bits()])
+            }
+        }
+    }
+}
+
+/// Tessellate the stroke of the path.
 ///
 /// adapted from [bevy_prototype_lyon](https://github.com/Nilirad/bevy_prototype_lyon/blob/master/src/path.rs)
-pub fn stroke(
-    path: lyon::path::Path,
-    material: Handle<ColorMaterial>,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    translation: Vec3,
-    options: &StrokeOptions,
-) -> SpriteBundle {
+pub fn stroke(path: &Path, options: &StrokeOptions) -> Result<Geometry, TessellationError> {
     let mut tessellator = StrokeTessellator::new();
-    let mut geometry = Geometry(VertexBuffers::new());
-    tessellator
-        .tessellate_path(
-            path.as_slice(),
-            options,
-            &mut BuffersBuilder::new(&mut geometry.0, |pos: StrokeVertex| {
-                [pos.position().x, pos.position().y, 0.0]
-            }),
-        )
-        .unwrap();
-
-    create_sprite(material, meshes, geometry, translation)
+    let mut geometry = Geometry::default();
+    tessellator.tessellate_path(
+        path.as_slice(),
+        options,
+        &mut BuffersBuilder::new(&mut geometry.buffers, |pos: StrokeVertex| {
+            [pos.position().x, pos.position().y, 0.0]
+        }),
+    )?;
+
+    Ok(geometry)
 }
 
-/// Fill to bevy components.
+/// Tessellate the fill of the path.
 ///
 /// adapted from [bevy_prototype_lyon](https://github.com/Nilirad/bevy_prototype_lyon/blob/master/src/path.rs)
-pub fn fill(
-    path: lyon::path::Path,
-    material: Handle<ColorMaterial>,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    translation: Vec3,
-    options: &FillOptions,
-) -> SpriteBundle {
+pub fn fill(path: &Path, options: &FillOptions) -> Result<Geometry, TessellationError> {
     let mut tessellator = FillTessellator::new();
-    let mut geometry = Geometry(VertexBuffers::new());
-    tessellator
-        .tessellate_path(
-            path.as_slice(),
-            options,
-            &mut BuffersBuilder::new(&mut geometry.0, |pos: FillVertex| {
-                [pos.position().x, pos.position().y, 0.0]
-            }),
-        )
-        .unwrap();
-
-    create_sprite(material, meshes, geometry, translation)
+    let mut geometry = Geometry::default();
+    tessellator.tessellate_path(
+        path.as_slice(),
+        options,
+        &mut BuffersBuilder::new(&mut geometry.buffers, |pos: FillVertex| {
+            [pos.position().x, pos.position().y, 0.0]
+        }),
+    )?;
+
+    Ok(geometry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::math::point;
+    use lyon::tessellation::FillRule;
+
+    const TOLERANCE: f32 = StrokeOptions::DEFAULT_TOLERANCE;
+
+    /// Start and end of each subpath
+    fn dashes(path: &Path) -> Vec<(f32, f32)> {
+        let mut dashes = Vec::new();
+        for event in path.iter() {
+            match event {
+                PathEvent::Begin { at } => dashes.push((at.x, at.x)),
+                PathEvent::Line { to, .. } => dashes.last_mut().unwrap().1 = to.x,
+                _ => {}
+            }
+        }
+        dashes
+    }
+
+    fn line(length: f32) -> Path {
+        let mut builder = Path::builder();
+        builder.begin(point(0., 0.));
+        builder.line_to(point(length, 0.));
+        builder.end(false);
+        builder.build()
+    }
+
+    #[test]
+    fn odd_dasharray_is_repeated() {
+        assert_eq!(
+            dashes(&dash(&line(10.), &[3.], 0., TOLERANCE)),
+            [(0., 3.), (6., 9.)]
+        );
+    }
+
+    #[test]
+    fn dashoffset_shifts_the_pattern() {
+        assert_eq!(
+            dashes(&dash(&line(10.), &[3., 3.], 2., TOLERANCE)),
+            [(0., 1.), (4., 7.)]
+        );
+        assert_eq!(
+            dashes(&dash(&line(10.), &[3., 3.], -1., TOLERANCE)),
+            [(1., 4.), (7., 10.)]
+        );
+    }
+
+    #[test]
+    fn dashes_follow_the_tolerance() {
+        let mut builder = Path::builder();
+        builder.begin(point(0., 0.));
+        builder.quadratic_bezier_to(point(50., 100.), point(100., 0.));
+        builder.end(false);
+        let curve = builder.build();
+        let segments = |tolerance| dash(&curve, &[1000.], 0., tolerance).iter().count();
+        assert!(segments(1.) < segments(0.01));
+    }
+
+    #[test]
+    fn fill_rule_makes_holes() {
+        // two squares drawn in the same direction, one inside the other
+        let mut builder = Path::builder();
+        for &(min, max) in &[(0., 4.), (1., 3.)] {
+            builder.begin(point(min, min));
+            builder.line_to(point(max, min));
+            builder.line_to(point(max, max));
+            builder.line_to(point(min, max));
+            builder.end(true);
+        }
+        let path = builder.build();
+        let area = |rule| {
+            let geometry = fill(&path, &FillOptions::default().with_fill_rule(rule)).unwrap();
+            let v = &geometry.buffers.vertices;
+            geometry
+                .buffers
+                .indices
+                .chunks(3)
+                .map(|t| {
+                    let (a, b, c) = (v[t[0] as usize], v[t[1] as usize], v[t[2] as usize]);
+                    ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.
+                })
+                .sum::<f32>()
+        };
+        assert_eq!(area(FillRule::NonZero), 16.);
+        assert_eq!(area(FillRule::EvenOdd), 12.);
+    }
+
+    #[test]
+    fn subdivision_has_no_cracks() {
+        let mut builder = Path::builder();
+        builder.begin(point(0., 0.));
+        builder.line_to(point(4., 0.));
+        builder.line_to(point(4., 1.));
+        builder.line_to(point(0., 1.));
+        builder.end(true);
+        let geometry = fill(&builder.build(), &FillOptions::default()).unwrap();
+        let before = geometry.buffers.indices.len();
+        // only the edges around (1, 0) are split, so the neighbours of the small triangles must
+        // be split too
+        let geometry = geometry.subdivide(|a, b| {
+            let middle = ((a[0] + b[0]) / 2. - 1.).hypot((a[1] + b[1]) / 2.);
+            (a[0] - b[0]).hypot(a[1] - b[1]) > 0.1 && middle < 1.5
+        });
+        assert!(geometry.buffers.indices.len() > 10 * before);
+        // an edge is either inside the mesh, shared by two triangles, or on its outline
+        let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+        for t in geometry.buffers.indices.chunks(3) {
+            for &(a, b) in &[(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+        let v = &geometry.buffers.vertices;
+        let outline: f32 = edges
+            .iter()
+            .filter(|(_, &count)| count == 1)
+            .map(|(&(a, b), _)| {
+                let (a, b) = (v[a as usize], v[b as usize]);
+                (a[0] - b[0]).hypot(a[1] - b[1])
+            })
+            .sum();
+        assert!(edges.values().all(|&count| count <= 2));
+        assert!((outline - 10.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn invalid_dasharray_is_solid() {
+        assert_eq!(
+            dashes(&dash(&line(10.), &[3., -1.], 0., TOLERANCE)),
+            [(0., 10.)]
+        );
+        assert_eq!(
+            dashes(&dash(&line(10.), &[0., 0.], 0., TOLERANCE)),
+            [(0., 10.)]
+        );
+    }
 }
@@ -0,0 +1,190 @@
+//! Mapping from the user space of the SVG document to the bevy world.
+use crate::options::{MapOptions, WorldOrigin};
+use crate::units::{Axis, Units};
+use bevy::math::Vec2;
+use euclid::default::{Point2D, Transform2D, Vector2D};
+use std::str::FromStr;
+use svgtypes::{Align, AspectRatio, ViewBox};
+
+/// Transformation from the user space of the root `<svg>` element to world coordinates.
+///
+/// The document is first fitted into its viewport following the `viewBox`, `width`,
+/// `height` and `preserveAspectRatio` attributes, as described in the
+/// [SVG spec](https://www.w3.org/TR/SVG11/coords.html#ViewBoxAttribute), and then placed
+/// in the world according to the [`MapOptions`](crate::MapOptions).
+///
+/// # Example
+///
+/// ```
+/// use bevy::math::Vec2;
+/// use bevy_svg_map::{MapOptions, MapTransform};
+///
+/// // a 20x20 document showing the square (0, 0) - (10, 10)
+/// let map = MapTransform::new(
+///     Vec2::new(20., 20.),
+///     Some([0., 0., 10., 10.]),
+///     Default::default(),
+///     &MapOptions::default(),
+/// );
+/// // the top-left corner of the document ends up at the top-left of the world
+/// assert_eq!(map.to_world(Vec2::new(0., 0.)), Vec2::new(-10., 10.));
+/// assert_eq!(map.size(), Vec2::new(20., 20.));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MapTransform {
+    transform: Transform2D<f32>,
+    size: Vec2,
+}
+
+impl MapTransform {
+    /// Build the mapping from the size of the viewport (`width` and `height` of the document),
+    /// the `viewBox` as `[x, y, width, height]` and its `preserveAspectRatio`.
+    pub fn new(
+        viewport: Vec2,
+        view_box: Option<[f32; 4]>,
+        aspect: AspectRatio,
+        options: &MapOptions,
+    ) -> Self {
+        let to_viewport = match view_box {
+            Some(view_box) => view_box_transform(view_box, viewport, aspect),
+            None => Transform2D::identity(),
+        };
+        let offset = match options.origin {
+            WorldOrigin::Center => Vector2D::new(viewport.x / 2., viewport.y / 2.),
+            WorldOrigin::TopLeft => Vector2D::zero(),
+            WorldOrigin::BottomLeft => Vector2D::new(0., viewport.y),
+        };
+        let flip = if options.flip_y { -1f32 } else { 1f32 };
+        MapTransform {
+            transform: to_viewport
+                .then_translate(-offset)
+                .then_scale(1. / options.pixels_per_unit, flip / options.pixels_per_unit),
+            size: viewport / options.pixels_per_unit,
+        }
+    }
+
+    /// Read the mapping from the attributes of the root `<svg>` element.
+    pub(crate) fn from_root(root: &roxmltree::Node, options: &MapOptions) -> Self {
+        let units = Units::from_root(root, options.dpi);
+        let view_box = root
+            .attribute("viewBox")
+            .and_then(|v| ViewBox::from_str(v).ok())
+            .filter(|vb| vb.w > 0. && vb.h > 0.)
+            .map(|vb| [vb.x as f32, vb.y as f32, vb.w as f32, vb.h as f32]);
+        let viewport = match options.world_units_per_user_unit {
+            Some(scale) => {
+                let (w, h) = units.view_box;
+                Vec2::new(w, h) * scale * options.pixels_per_unit
+            }
+            None => {
+                let length = |attr: &str, axis: Axis| {
+                    root.attribute(attr).and_then(|v| units.parse(v, axis))
+                };
+                Vec2::new(
+                    length("width", Axis::X)
+                        .or_else(|| view_box.map(|vb| vb[2]))
+                        .unwrap_or(0.),
+                    length("height", Axis::Y)
+                        .or_else(|| view_box.map(|vb| vb[3]))
+                        .unwrap_or(0.),
+                )
+            }
+        };
+        let aspect = root
+            .attribute("preserveAspectRatio")
+            .and_then(|v| AspectRatio::from_str(v).ok())
+            .unwrap_or_default();
+        Self::new(viewport, view_box, aspect, options)
+    }
+
+    /// Map a point in the user space of the document to world coordinates.
+    pub fn to_world(&self, point: Vec2) -> Vec2 {
+        let p = self
+            .transform
+            .transform_point(Point2D::new(point.x, point.y));
+        Vec2::new(p.x, p.y)
+    }
+
+    /// Size of the document in world units.
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    pub(crate) fn transform(&self) -> &Transform2D<f32> {
+        &self.transform
+    }
+}
+
+/// Transformation from the viewBox to the viewport.
+/// See: https://www.w3.org/TR/SVG11/coords.html#PreserveAspectRatioAttribute
+fn view_box_transform(view_box: [f32; 4], viewport: Vec2, aspect: AspectRatio) -> Transform2D<f32> {
+    let [x, y, w, h] = view_box;
+    let (sx, sy) = (viewport.x / w, viewport.y / h);
+    let (sx, sy) = match aspect.align {
+        Align::None => (sx, sy),
+        _ if aspect.slice => (sx.max(sy), sx.max(sy)),
+        _ => (sx.min(sy), sx.min(sy)),
+    };
+    let (ax, ay) = match aspect.align {
+        Align::None | Align::XMinYMin => (0., 0.),
+        Align::XMidYMin => (0.5, 0.),
+        Align::XMaxYMin => (1., 0.),
+        Align::XMinYMid => (0., 0.5),
+        Align::XMidYMid => (0.5, 0.5),
+        Align::XMaxYMid => (1., 0.5),
+        Align::XMinYMax => (0., 1.),
+        Align::XMidYMax => (0.5, 1.),
+        Align::XMaxYMax => (1., 1.),
+    };
+    Transform2D::scale(sx, sy).then_translate(Vector2D::new(
+        -x * sx + (viewport.x - w * sx) * ax,
+        -y * sy + (viewport.y - h * sy) * ay,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(svg: &str, options: &MapOptions) -> MapTransform {
+        let doc = roxmltree::Document::parse(svg).unwrap();
+        MapTransform::from_root(&doc.root_element(), options)
+    }
+
+    #[test]
+    fn view_box_meet_is_centered() {
+        let map = map(
+            r#"<svg width="40" height="20" viewBox="0 0 10 10"/>"#,
+            &MapOptions::default(),
+        );
+        assert_eq!(map.to_world(Vec2::new(0., 0.)), Vec2::new(-10., 10.));
+        assert_eq!(map.to_world(Vec2::new(10., 10.)), Vec2::new(10., -10.));
+    }
+
+    #[test]
+    fn bottom_left_with_scale() {
+        let options = MapOptions {
+            origin: WorldOrigin::BottomLeft,
+            pixels_per_unit: 2.,
+            ..Default::default()
+        };
+        let map = map(r#"<svg viewBox="-5 -5 10 10"/>"#, &options);
+        assert_eq!(map.to_world(Vec2::new(-5., 5.)), Vec2::new(0., 0.));
+        assert_eq!(map.to_world(Vec2::new(5., -5.)), Vec2::new(5., 5.));
+        assert_eq!(map.size(), Vec2::new(5., 5.));
+    }
+
+    #[test]
+    fn document_in_millimetres() {
+        let svg = r#"<svg width="25.4mm" height="50.8mm" viewBox="0 0 25.4 50.8"/>"#;
+        assert_eq!(
+            map(svg, &MapOptions::default()).size(),
+            Vec2::new(96., 192.)
+        );
+        let options = MapOptions {
+            world_units_per_user_unit: Some(10.),
+            ..Default::default()
+        };
+        assert_eq!(map(svg, &options).size(), Vec2::new(254., 508.));
+    }
+}
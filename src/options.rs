@@ -0,0 +1,77 @@
+use std::ops::Range;
+
+/// Point of the SVG document that is placed at the origin of the bevy world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorldOrigin {
+    /// Center of the document.
+    Center,
+    /// Top-left corner of the document (the origin of the SVG viewport).
+    TopLeft,
+    /// Bottom-left corner of the document.
+    BottomLeft,
+}
+
+/// What to do with the elements that are not rendered (`display:none`, e.g. hidden Inkscape
+/// layers, or `visibility:hidden`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HiddenElements {
+    /// They are not spawned.
+    Skip,
+    /// They are spawned with `Visible { is_visible: false, .. }`, e.g. to show them later.
+    Invisible,
+}
+
+/// Options that control how a SVG map is placed into the bevy world.
+///
+/// # Example
+///
+/// ```
+/// use bevy_svg_map::{MapOptions, WorldOrigin};
+///
+/// let options = MapOptions {
+///     origin: WorldOrigin::BottomLeft,
+///     pixels_per_unit: 32.,
+///     ..Default::default()
+/// };
+/// assert!(options.flip_y);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MapOptions {
+    /// Which point of the document ends up at (0, 0).
+    pub origin: WorldOrigin,
+    /// SVG y-axis points down while bevy's points up. Flip it to keep the map as it looks
+    /// in the editor.
+    pub flip_y: bool,
+    /// Number of document pixels that make a world unit.
+    pub pixels_per_unit: f32,
+    /// Pixels in one inch, used to convert absolute units (in, cm, mm, pt, pc) to pixels.
+    /// CSS defines it as 96, but older Inkscape documents use 90.
+    pub dpi: f32,
+    /// When set, the physical size of the document (`width` and `height`) is ignored and
+    /// each SVG user unit becomes this amount of world units.
+    pub world_units_per_user_unit: Option<f32>,
+    /// The fills and strokes of the map are spread over this range of z in the order in which
+    /// they are painted, so that the ones that come later in the document are drawn on top.
+    pub z_range: Range<f32>,
+    /// Extra distance in z between consecutive Inkscape layers, added to the z that the parts
+    /// get from `z_range`, e.g. to leave room for the entities of the game between layers.
+    /// It is 0 by default, so that the whole map stays within `z_range`.
+    pub layer_depth: f32,
+    /// What to do with the elements that are not rendered.
+    pub hidden: HiddenElements,
+}
+
+impl Default for MapOptions {
+    fn default() -> Self {
+        MapOptions {
+            origin: WorldOrigin::Center,
+            flip_y: true,
+            pixels_per_unit: 1.,
+            dpi: 96.,
+            world_units_per_user_unit: None,
+            z_range: 0.0..1.0,
+            layer_depth: 0.,
+            hidden: HiddenElements::Skip,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Pipeline that draws meshes with vertex colors.
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{PipelineDescriptor, RenderPipeline},
+        shader::{ShaderStage, ShaderStages},
+    },
+    sprite::build_sprite_pipeline,
+};
+
+/// Handle of the pipeline registered by the [`SvgMapPlugin`](crate::SvgMapPlugin) to draw the
+/// parts with vertex colors, see [`StyleStrategy::vertex_color_decider`](crate::StyleStrategy).
+pub const VERTEX_COLOR_PIPELINE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(PipelineDescriptor::TYPE_UUID, 3_816_431_727_158_406_912);
+
+/// Same as the sprite shader, passing the color of the vertices to the fragment shader
+const VERTEX_SHADER: &str = r#"
+#version 450
+
+layout(location = 0) in vec3 Vertex_Position;
+layout(location = 1) in vec4 Vertex_Color;
+
+layout(location = 0) out vec4 v_Color;
+
+layout(set = 0, binding = 0) uniform CameraViewProj {
+    mat4 ViewProj;
+};
+
+layout(set = 2, binding = 0) uniform Transform {
+    mat4 Model;
+};
+layout(set = 2, binding = 1) uniform Sprite {
+    vec2 size;
+    uint flip;
+};
+
+void main() {
+    v_Color = Vertex_Color;
+    vec3 position = Vertex_Position * vec3(size, 1.0);
+    gl_Position = ViewProj * Model * vec4(position, 1.0);
+}
+"#;
+
+/// The color of the vertex, tinted by the `ColorMaterial`
+const FRAGMENT_SHADER: &str = r#"
+#version 450
+
+layout(location = 0) in vec4 v_Color;
+
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 0) uniform ColorMaterial_color {
+    vec4 Color;
+};
+
+void main() {
+    o_Target = Color * v_Color;
+}
+"#;
+
+/// The sprite pipeline (same blending and depth test) with the vertex color shaders
+pub(crate) fn build_vertex_color_pipeline(shaders: &mut Assets<Shader>) -> PipelineDescriptor {
+    PipelineDescriptor {
+        shader_stages: ShaderStages {
+            vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, VERTEX_SHADER)),
+            fragment: Some(shaders.add(Shader::from_glsl(ShaderStage::Fragment, FRAGMENT_SHADER))),
+        },
+        ..build_sprite_pipeline(shaders)
+    }
+}
+
+/// Make the sprite draw its mesh with vertex colors
+pub(crate) fn use_vertex_colors(sprite: &mut SpriteBundle) {
+    sprite.render_pipelines = RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+        VERTEX_COLOR_PIPELINE_HANDLE.typed(),
+    )]);
+}
@@ -0,0 +1,177 @@
+//! Conversion of SVG basic shapes into path data.
+//!
+//! Every [basic shape](https://www.w3.org/TR/SVG11/shapes.html) is translated into the
+//! equivalent "d" attribute, so it can be parsed by lyon exactly like a `<path>`.
+use crate::units::{Axis, Units};
+use svgtypes::PointsParser;
+
+/// Parse a length attribute of the shape in user units, defaulting to 0 when missing or invalid.
+fn number(node: &roxmltree::Node, attr: &str, units: &Units) -> f32 {
+    let axis = match attr {
+        "x" | "cx" | "x1" | "x2" | "width" | "rx" => Axis::X,
+        "y" | "cy" | "y1" | "y2" | "height" | "ry" => Axis::Y,
+        _ => Axis::Other,
+    };
+    node.attribute(attr)
+        .and_then(|s| units.parse(s, axis))
+        .unwrap_or(0f32)
+}
+
+/// Parse the radius attributes of a `<rect>` or `<ellipse>` (`rx` and `ry`).
+/// If only one of them is present, the other one takes the same value.
+fn radii(node: &roxmltree::Node, units: &Units) -> (f32, f32) {
+    let rx = node.attribute("rx").map(|_| number(node, "rx", units));
+    let ry = node.attribute("ry").map(|_| number(node, "ry", units));
+    match (rx, ry) {
+        (Some(rx), Some(ry)) => (rx, ry),
+        (Some(r), None) | (None, Some(r)) => (r, r),
+        _ => (0f32, 0f32),
+    }
+}
+
+fn rect(node: &roxmltree::Node, units: &Units) -> Option<String> {
+    let (x, y) = (number(node, "x", units), number(node, "y", units));
+    let (w, h) = (number(node, "width", units), number(node, "height", units));
+    if w <= 0f32 || h <= 0f32 {
+        return None;
+    }
+    let (rx, ry) = radii(node, units);
+    let (rx, ry) = (rx.max(0f32).min(w / 2f32), ry.max(0f32).min(h / 2f32));
+    if rx == 0f32 || ry == 0f32 {
+        return Some(format!("M {} {} H {} V {} H {} Z", x, y, x + w, y + h, x));
+    }
+    Some(format!(
+        "M {x0} {y} H {x1} A {rx} {ry} 0 0 1 {xw} {y0} V {y1} A {rx} {ry} 0 0 1 {x1} {yh} \
+         H {x0} A {rx} {ry} 0 0 1 {x} {y1} V {y0} A {rx} {ry} 0 0 1 {x0} {y} Z",
+        x = x,
+        y = y,
+        rx = rx,
+        ry = ry,
+        x0 = x + rx,
+        x1 = x + w - rx,
+        xw = x + w,
+        y0 = y + ry,
+        y1 = y + h - ry,
+        yh = y + h,
+    ))
+}
+
+/// Both circles and ellipses are drawn as two half arcs.
+fn ellipse(cx: f32, cy: f32, rx: f32, ry: f32) -> Option<String> {
+    if rx <= 0f32 || ry <= 0f32 {
+        return None;
+    }
+    Some(format!(
+        "M {x0} {cy} A {rx} {ry} 0 1 1 {x1} {cy} A {rx} {ry} 0 1 1 {x0} {cy} Z",
+        x0 = cx + rx,
+        x1 = cx - rx,
+        cy = cy,
+        rx = rx,
+        ry = ry,
+    ))
+}
+
+fn line(node: &roxmltree::Node, units: &Units) -> Option<String> {
+    Some(format!(
+        "M {} {} L {} {}",
+        number(node, "x1", units),
+        number(node, "y1", units),
+        number(node, "x2", units),
+        number(node, "y2", units)
+    ))
+}
+
+fn poly(node: &roxmltree::Node, closed: bool) -> Option<String> {
+    let mut points = PointsParser::from(node.attribute("points")?);
+    let (x, y) = points.next()?;
+    let mut d = format!("M {} {}", x, y);
+    for (x, y) in points {
+        d.push_str(&format!(" L {} {}", x, y));
+    }
+    if closed {
+        d.push_str(" Z");
+    }
+    Some(d)
+}
+
+/// Elements whose content is only drawn where they are referenced, e.g. a `<clipPath>`
+const NOT_RENDERED: &[&str] = &["clipPath", "defs", "marker", "mask", "pattern", "symbol"];
+
+/// Whether the node is drawn where it is in the document, that is, it is not inside a
+/// `<defs>`, `<clipPath>`, `<mask>`, `<pattern>`, `<marker>` or `<symbol>`.
+pub fn is_rendered(node: &roxmltree::Node) -> bool {
+    !node
+        .ancestors()
+        .any(|n| NOT_RENDERED.contains(&n.tag_name().name()))
+}
+
+/// Path data of a node, either its "d" attribute or the outline of a basic shape.
+///
+/// Returns `None` for any other element or if the shape would not be rendered
+/// (e.g., a rect without width). Lengths are converted to user units.
+pub fn path_data<'a>(
+    node: &roxmltree::Node<'a, '_>,
+    units: &Units,
+) -> Option<std::borrow::Cow<'a, str>> {
+    if let Some(d) = node.attribute("d") {
+        return Some(d.into());
+    }
+    match node.tag_name().name() {
+        "rect" => rect(node, units),
+        "circle" => {
+            let r = number(node, "r", units);
+            ellipse(number(node, "cx", units), number(node, "cy", units), r, r)
+        }
+        "ellipse" => {
+            let (rx, ry) = radii(node, units);
+            ellipse(number(node, "cx", units), number(node, "cy", units), rx, ry)
+        }
+        "line" => line(node, units),
+        "polyline" => poly(node, false),
+        "polygon" => poly(node, true),
+        _ => None,
+    }
+    .map(|d| d.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_shape(svg: &str) -> Option<String> {
+        let doc = roxmltree::Document::parse(svg).unwrap();
+        let node = doc.root_element().first_element_child().unwrap();
+        path_data(&node, &Units::default()).map(|d| d.into_owned())
+    }
+
+    #[test]
+    fn rect_to_path() {
+        assert_eq!(
+            first_shape(r#"<svg><rect x="1" y="2" width="3" height="4"/></svg>"#).unwrap(),
+            "M 1 2 H 4 V 6 H 1 Z"
+        );
+    }
+
+    #[test]
+    fn rounded_rect_clamps_radius() {
+        let d = first_shape(r#"<svg><rect width="4" height="2" rx="3"/></svg>"#).unwrap();
+        assert!(d.starts_with("M 2 0 H 2 A 2 1 0 0 1 4 1"));
+    }
+
+    #[test]
+    fn polygon_is_closed() {
+        assert_eq!(
+            first_shape(r#"<svg><polygon points="0,0 1,0 1,1"/></svg>"#).unwrap(),
+            "M 0 0 L 1 0 L 1 1 Z"
+        );
+        assert!(first_shape(r#"<svg><circle r="0"/></svg>"#).is_none());
+    }
+
+    #[test]
+    fn shape_with_units() {
+        assert_eq!(
+            first_shape(r#"<svg><line x2="1in" y2="3pt"/></svg>"#).unwrap(),
+            "M 0 0 L 96 4"
+        );
+    }
+}
@@ -1,27 +1,22 @@
+use crate::color::{parse_alpha, parse_color};
+use crate::css::{parse_declarations, Stylesheet};
+use crate::error::{StyleError, SvgMapError};
+use crate::gradient::{Gradient, Gradients};
+use crate::transform::node_transform;
+use crate::units::{Axis, Units};
+use crate::PartKind;
 use bevy::prelude::Color;
-use lyon::lyon_tessellation::{LineCap, LineJoin};
+use euclid::default::Transform2D;
+use lyon::lyon_tessellation::{FillOptions, FillRule, LineCap, LineJoin, StrokeOptions};
+use lyon::math::Rect;
 use std::collections::HashMap;
-use std::str::FromStr;
-use svgtypes::{Length, NumberList, Paint};
+use svgtypes::{LengthListParser, NumberList};
 
-/// Adapted from bevy_render
-fn linear_to_nonlinear_srgb(num: f32) -> u8 {
-    let res: f32;
-    if num <= 0.0 || num >= 0.99 {
-        res = num;
-    } else if num <= 0.0031308 {
-        res = num * 12.92; // linear falloff in dark values
-    } else {
-        res = (1.055 * num.powf(1.0 / 2.4)) - 0.055 //gamma curve in other area
-    }
-    (res * 255.0) as u8
-}
-/// Helper function that transforms from str to svgtypes' Color to bevy's Color
-fn to_color(color: &str, opacity: u8) -> Option<Color> {
-    if let Ok(Paint::Color(svgtypes::Color { red, green, blue })) = Paint::from_str(color) {
-        Some(Color::rgba_u8(red as u8, green as u8, blue as u8, opacity))
-    } else {
-        None
+fn parse_fill_rule(rule: &str) -> Option<FillRule> {
+    match rule {
+        "nonzero" => Some(FillRule::NonZero),
+        "evenodd" => Some(FillRule::EvenOdd),
+        _ => None,
     }
 }
 
@@ -31,15 +26,31 @@ fn to_color(color: &str, opacity: u8) -> Option<Color> {
 pub struct StyleSegment {
     pub style: SvgStyle,
     pub traces: String,
+    /// Composed `transform` of the element and its ancestors
+    pub transform: Transform2D<f32>,
+    /// Index of the closest group that contains the element
+    pub group: Option<usize>,
 }
 
-impl From<(&str, &str, Option<&str>, Option<&str>)> for StyleSegment {
-    fn from(tup: (&str, &str, Option<&str>, Option<&str>)) -> Self {
-        let mut style: SvgStyle = SvgStyle::from(tup.0);
-        style.id = tup.2.map(|s| s.to_owned());
-        style.class = tup.3.map(|s| s.to_owned());
-        let traces = tup.1.to_string();
-        StyleSegment { style, traces }
+/// The style is not validated, so that the elements that are skipped are not checked.
+impl From<(&roxmltree::Node<'_, '_>, &str, &Units, &Stylesheet)> for StyleSegment {
+    fn from(
+        (node, traces, units, stylesheet): (&roxmltree::Node, &str, &Units, &Stylesheet),
+    ) -> Self {
+        let (declarations, warnings) = stylesheet.computed(node);
+        let mut style = SvgStyle::from_declarations(declarations);
+        style.warnings = warnings;
+        style.units = stylesheet.units(node, units);
+        style.id = node.attribute("id").map(|s| s.to_owned());
+        style.class = node.attribute("class").map(|s| s.to_owned());
+        style.displayed = stylesheet.displayed(node);
+        let traces = traces.to_string();
+        StyleSegment {
+            style,
+            traces,
+            transform: node_transform(node),
+            group: None,
+        }
     }
 }
 
@@ -48,8 +59,11 @@ impl From<(&str, &str, Option<&str>, Option<&str>)> for StyleSegment {
 /// Chief struct to implement the user-provided strategy to associate components/materials given
 /// the style of the path.
 ///
-/// Except stroke_opacity() and fill_opacity() that return a `Result`, all the types return an
-/// `Option<T>`. More properties could be exposed but these are enough for now to build things.
+/// The properties return an `Option<T>`, `None` when they are missing or invalid; the two
+/// opacities are `Some(1.)` when missing. Some accessors have a default instead: `color()`
+/// (black), `opacity()` (1) and `paint_order()` (fill, then stroke) return the value itself,
+/// `is_visible()` is a `bool` and `warnings()` lists the declarations that were skipped.
+/// More properties could be exposed but these are enough for now to build things.
 ///
 /// # Example
 ///
@@ -67,31 +81,174 @@ impl From<(&str, &str, Option<&str>, Option<&str>)> for StyleSegment {
 pub struct SvgStyle {
     id: Option<String>,
     class: Option<String>,
+    pub(crate) layer: Option<String>,
+    /// False if the element or one of its ancestors has `display:none`
+    pub(crate) displayed: bool,
+    warnings: Vec<StyleError>,
     hash_style: HashMap<String, String>,
+    /// Gradients referenced by the `fill` and `stroke` properties, by property
+    gradients: HashMap<String, Gradient>,
+    units: Units,
+}
+
+/// How the fill or the stroke of a path is painted, see
+/// [`<paint>`](https://www.w3.org/TR/SVG2/painting.html#SpecifyingPaint)
+///
+/// ```
+/// use bevy_svg_map::Paint;
+/// use bevy::prelude::Color;
+///
+/// assert_eq!(Paint::parse("rgba(255, 0, 0, 0.5)"), Some(Paint::Color(Color::rgba(1., 0., 0., 0.5))));
+/// assert_eq!(
+///     Paint::parse("url(#pattern1) red"),
+///     Some(Paint::Url { id: "pattern1".to_owned(), fallback: Some(Box::new(Paint::Color(Color::RED))) })
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Paint {
+    /// `none`; also `context-fill` and `context-stroke`, since markers are not drawn
+    None,
+    Color(Color),
+    /// A reference to something that is not a gradient of the document (e.g. a pattern),
+    /// painted with the fallback, if any
+    Url {
+        id: String,
+        fallback: Option<Box<Paint>>,
+    },
+    /// The `color` property; resolved by [`SvgStyle`]
+    CurrentColor,
+    Gradient(Gradient),
+}
+
+impl Paint {
+    /// Parse the value of `fill` or `stroke`
+    pub fn parse(text: &str) -> Option<Paint> {
+        let text = text.trim();
+        if let Some(url) = text.strip_prefix("url(") {
+            let close = url.find(')')?;
+            let id = url[..close].trim().trim_matches(|c| c == '\'' || c == '"');
+            let fallback = url[close + 1..].trim();
+            return Some(Paint::Url {
+                id: id.strip_prefix('#').unwrap_or(id).to_owned(),
+                fallback: match fallback {
+                    "" => None,
+                    fallback if fallback.starts_with("url(") => return None,
+                    fallback => Some(Box::new(Paint::parse(fallback)?)),
+                },
+            });
+        }
+        match text {
+            "none" | "context-fill" | "context-stroke" => Some(Paint::None),
+            _ if text.eq_ignore_ascii_case("currentColor") => Some(Paint::CurrentColor),
+            _ => parse_color(text).map(Paint::Color),
+        }
+    }
+
+    /// The color, the fallback of an unresolved reference or the mean color of the stops of
+    /// the gradient; `None` if nothing is painted
+    pub fn color(&self) -> Option<Color> {
+        match self {
+            Paint::None | Paint::CurrentColor => None,
+            Paint::Color(color) => Some(*color),
+            Paint::Url { fallback, .. } => fallback.as_ref().and_then(|f| f.color()),
+            Paint::Gradient(gradient) => Some(gradient.average()),
+        }
+    }
+
+    /// Multiply the alpha of the paint
+    fn with_opacity(self, opacity: f32) -> Self {
+        match self {
+            Paint::Color(mut color) => {
+                color.set_a(color.a() * opacity);
+                Paint::Color(color)
+            }
+            Paint::Url { id, fallback } => Paint::Url {
+                id,
+                fallback: fallback.map(|f| Box::new(f.with_opacity(opacity))),
+            },
+            Paint::Gradient(gradient) => Paint::Gradient(gradient.with_opacity(opacity)),
+            paint => paint,
+        }
+    }
 }
 
 impl SvgStyle {
+    /// Color of the stroke; for a gradient, the mean color of its stops
     pub fn stroke(&self) -> Option<Color> {
-        to_color(
-            self.panic_access("stroke"),
-            match self.stroke_opacity() {
-                Ok(c) => linear_to_nonlinear_srgb(c),
-                _ => 255,
-            },
-        )
+        self.stroke_paint()?.color()
     }
+    /// Color of the fill; for a gradient, the mean color of its stops
     pub fn fill(&self) -> Option<Color> {
-        to_color(
-            self.panic_access("fill"),
-            match self.fill_opacity() {
-                Ok(c) => linear_to_nonlinear_srgb(c),
-                _ => 255,
+        self.fill_paint()?.color()
+    }
+    /// Paint of the stroke, see [`SvgStyle::fill_paint`]
+    pub fn stroke_paint(&self) -> Option<Paint> {
+        self.paint("stroke", self.stroke_opacity().unwrap_or(1.))
+    }
+    /// Paint of the fill, with `currentColor` and the gradients of the document resolved and
+    /// the alpha multiplied by `fill-opacity` and `opacity`. A `url(#id)` that is not a
+    /// gradient of the document is kept with its fallback.
+    ///
+    /// ```
+    /// use bevy_svg_map::{Paint, SvgStyle};
+    /// use bevy::prelude::Color;
+    ///
+    /// let style = SvgStyle::from("fill:currentColor;color:#0000ff;fill-opacity:0.5;opacity:0.5");
+    /// assert_eq!(style.fill_paint(), Some(Paint::Color(Color::rgba(0., 0., 1., 0.25))));
+    ///
+    /// let style = SvgStyle::from("fill:url(#missing) #ff0000");
+    /// assert_eq!(style.fill(), Some(Color::RED));
+    /// ```
+    pub fn fill_paint(&self) -> Option<Paint> {
+        self.paint("fill", self.fill_opacity().unwrap_or(1.))
+    }
+    fn paint(&self, property: &str, opacity: f32) -> Option<Paint> {
+        let paint = match Paint::parse(self.hash_style.get(property)?)? {
+            Paint::CurrentColor => Paint::Color(self.color()),
+            Paint::Url { id, fallback } => match self.gradients.get(property) {
+                Some(gradient) => Paint::Gradient(gradient.clone()),
+                None => Paint::Url {
+                    id,
+                    fallback: fallback.map(|f| match *f {
+                        Paint::CurrentColor => Box::new(Paint::Color(self.color())),
+                        f => Box::new(f),
+                    }),
+                },
             },
-        )
+            paint => paint,
+        };
+        Some(paint.with_opacity((opacity * self.opacity()).clamp(0., 1.)))
+    }
+    /// The `color` property, used by `currentColor`; black by default
+    pub fn color(&self) -> Color {
+        self.hash_style
+            .get("color")
+            .and_then(|c| parse_color(c))
+            .unwrap_or(Color::BLACK)
+    }
+    /// Opacity of the whole element, the `opacity` property
+    pub fn opacity(&self) -> f32 {
+        self.hash_style
+            .get("opacity")
+            .and_then(|o| parse_alpha(o))
+            .unwrap_or(1.)
+    }
+    /// Resolve the gradients referenced by `url(#id)` in the fill and the stroke
+    pub(crate) fn resolve_gradients(&mut self, gradients: &Gradients) {
+        for property in &["fill", "stroke"] {
+            let paint = self.hash_style.get(*property).and_then(|p| Paint::parse(p));
+            if let Some(Paint::Url { id, .. }) = paint {
+                if let Some(gradient) = gradients.get(&id) {
+                    self.gradients
+                        .insert((*property).to_owned(), gradient.clone());
+                }
+            }
+        }
     }
     /// The resulting [`svgtypes::NumberList`](https://docs.rs/svgtypes/0.5.0/src/svgtypes/number_list.rs.html)
-    /// can be treated as Vec<f64>
-    /// See: [<list-of-numbers>](https://www.w3.org/TR/SVG11/types.html#DataTypeList)
+    /// can be treated as Vec<f64>. Like `stroke-width`, the dashes are lengths, converted
+    /// into user units.
+    /// See: [<list-of-lengths>](https://www.w3.org/TR/SVG11/types.html#DataTypeList)
     /// ```
     /// # use bevy_svg_map::SvgStyle;
     /// # use bevy::prelude::Color;
@@ -113,11 +270,23 @@ impl SvgStyle {
     /// assert_eq!(style.stroke_dasharray().unwrap().iter().sum::<f64>(), 4f64);
     /// ```
     pub fn stroke_dasharray(&self) -> Option<NumberList> {
-        self.hash_style
-            .get("stroke-dasharray")
-            .map(|c| c.parse().unwrap())
+        LengthListParser::from(self.hash_style.get("stroke-dasharray")?.as_str())
+            .map(|length| Some(self.units.length(length.ok()?, Axis::Other) as f64))
+            .collect::<Option<Vec<f64>>>()
+            .map(NumberList)
     }
-    /// In both opacities, please remember that they return a Result (it may change in the future)
+    /// Offset into the dash pattern at which the stroke starts, in user units
+    /// ```
+    /// # use bevy_svg_map::SvgStyle;
+    ///
+    /// let style = SvgStyle::from("stroke:#000000;stroke-dasharray:3,1;stroke-dashoffset:2");
+    /// assert_eq!(style.stroke_dashoffset().unwrap(), 2.);
+    /// ```
+    pub fn stroke_dashoffset(&self) -> Option<f32> {
+        self.units
+            .parse(self.hash_style.get("stroke-dashoffset")?, Axis::Other)
+    }
+    /// Both opacities are `Some(1.)` when the property is missing and `None` when it is invalid
     /// ```
     /// # use bevy_svg_map::SvgStyle;
     /// # use bevy::prelude::Color;
@@ -128,24 +297,38 @@ impl SvgStyle {
     ///     0.5
     /// );
     /// ```
-    pub fn stroke_opacity(&self) -> Result<f32, std::num::ParseFloatError> {
-        match self.hash_style.get("stroke-opacity") {
-            Some(c) => c.parse(),
-            _ => Ok(1f32),
-        }
+    ///
+    /// Like `opacity`, they can be percentages and are clamped to `[0, 1]`.
+    /// ```
+    /// # use bevy_svg_map::SvgStyle;
+    ///
+    /// let style = SvgStyle::from("fill:#000000;fill-opacity:25%;stroke-opacity:2");
+    /// assert_eq!(style.fill_opacity().unwrap(), 0.25);
+    /// assert_eq!(style.stroke_opacity().unwrap(), 1.);
+    /// ```
+    pub fn stroke_opacity(&self) -> Option<f32> {
+        self.alpha("stroke-opacity")
+    }
+    pub fn fill_opacity(&self) -> Option<f32> {
+        self.alpha("fill-opacity")
     }
-    pub fn fill_opacity(&self) -> Result<f32, std::num::ParseFloatError> {
-        match self.hash_style.get("fill-opacity") {
-            Some(c) => c.parse(),
-            _ => Ok(1f32),
+    fn alpha(&self, property: &str) -> Option<f32> {
+        match self.hash_style.get(property) {
+            Some(c) => parse_alpha(c),
+            _ => Some(1f32),
         }
     }
+    /// Stroke width in user units; absolute (mm, in, pt...) and relative (em, %) units are
+    /// converted to pixels.
+    /// ```
+    /// # use bevy_svg_map::SvgStyle;
+    ///
+    /// let style = SvgStyle::from("stroke:#000000;stroke-width:0.5in");
+    /// assert_eq!(style.stroke_width().unwrap(), 48.);
+    /// ```
     pub fn stroke_width(&self) -> Option<f32> {
-        if let Ok(Length { num, unit: _ }) = Length::from_str(self.panic_access("stroke-width")) {
-            Some(num as f32)
-        } else {
-            None
-        }
+        self.units
+            .parse(self.hash_style.get("stroke-width")?, Axis::Other)
     }
     /// Parse the string as a Lyon LineCap
     /// See: https://svgwg.org/specs/strokes/#StrokeLinecapProperty
@@ -198,6 +381,41 @@ impl SvgStyle {
             _ => None,
         }
     }
+    /// Limit of the ratio between the length of a miter join and the stroke width, beyond
+    /// which the join is beveled. Values below 1 are invalid.
+    /// See: https://svgwg.org/specs/strokes/#StrokeMiterlimitProperty
+    ///
+    /// ```
+    /// # use bevy_svg_map::SvgStyle;
+    ///
+    /// let style = SvgStyle::from("stroke:#000000;stroke-miterlimit:10");
+    /// assert_eq!(style.stroke_miterlimit().unwrap(), 10.);
+    /// ```
+    pub fn stroke_miterlimit(&self) -> Option<f32> {
+        self.hash_style
+            .get("stroke-miterlimit")?
+            .parse()
+            .ok()
+            .filter(|limit| *limit >= 1.)
+    }
+    /// Parse the string as a Lyon FillRule
+    /// See: https://www.w3.org/TR/SVG2/painting.html#FillRuleProperty
+    ///
+    /// ```
+    /// # use bevy_svg_map::SvgStyle;
+    /// use lyon::lyon_tessellation::FillRule;
+    ///
+    /// let style = SvgStyle::from("fill:#000000;fill-rule:evenodd");
+    /// assert_eq!(style.fill_rule().unwrap(), FillRule::EvenOdd);
+    /// ```
+    pub fn fill_rule(&self) -> Option<FillRule> {
+        parse_fill_rule(self.hash_style.get("fill-rule")?)
+    }
+    /// Parse the string as a Lyon FillRule; it only matters for clipping paths, which are not
+    /// drawn, but it is exposed to the strategy
+    pub fn clip_rule(&self) -> Option<FillRule> {
+        parse_fill_rule(self.hash_style.get("clip-rule")?)
+    }
     /// Id of the path, if any
     pub fn id(&self) -> &Option<String> {
         &self.id
@@ -206,33 +424,125 @@ impl SvgStyle {
     pub fn class(&self) -> &Option<String> {
         &self.class
     }
-    fn panic_access(&self, key: &str) -> &str {
-        match self.hash_style.get(key) {
-            Some(value) => value,
-            _ => panic!(
-                "Field {} (used to build svg-based components) is missing! Check your SVG file",
-                key
-            ),
+    /// Order in which the fill and the stroke are painted, as specified by `paint-order`
+    pub fn paint_order(&self) -> [PartKind; 2] {
+        let order = self.hash_style.get("paint-order").map_or("normal", |s| s);
+        let position = |part| order.split_whitespace().position(|p| p == part);
+        match (position("fill"), position("stroke")) {
+            (None, Some(_)) => [PartKind::Stroke, PartKind::Fill],
+            (Some(fill), Some(stroke)) if stroke < fill => [PartKind::Stroke, PartKind::Fill],
+            _ => [PartKind::Fill, PartKind::Stroke],
+        }
+    }
+    /// Whether the path is rendered: neither it nor its ancestors have `display:none`, and its
+    /// `visibility` (inherited from the groups) is `visible`
+    ///
+    /// ```
+    /// # use bevy_svg_map::SvgStyle;
+    ///
+    /// assert!(SvgStyle::from("stroke:#000000").is_visible());
+    /// assert!(!SvgStyle::from("stroke:#000000;display:none").is_visible());
+    /// assert!(!SvgStyle::from("stroke:#000000;visibility:hidden").is_visible());
+    /// ```
+    pub fn is_visible(&self) -> bool {
+        self.displayed
+            && self.hash_style.get("display").map(|d| d.as_str()) != Some("none")
+            && matches!(
+                self.hash_style.get("visibility").map(|v| v.as_str()),
+                None | Some("visible")
+            )
+    }
+    /// Label of the Inkscape layer that contains the path, if any
+    pub fn layer(&self) -> &Option<String> {
+        &self.layer
+    }
+    /// Check that the numeric properties that are present can be parsed
+    pub(crate) fn validate(&self) -> Result<(), SvgMapError> {
+        let invalid = |property: &str, value: &str| SvgMapError::StyleSyntax {
+            id: self.id.clone(),
+            property: property.to_owned(),
+            value: value.to_owned(),
+        };
+        if let Some(value) = self.hash_style.get("stroke-width") {
+            self.stroke_width()
+                .ok_or_else(|| invalid("stroke-width", value))?;
+        }
+        if let Some(value) = self.hash_style.get("stroke-miterlimit") {
+            self.stroke_miterlimit()
+                .ok_or_else(|| invalid("stroke-miterlimit", value))?;
+        }
+        if let Some(value) = self.hash_style.get("stroke-dashoffset") {
+            self.stroke_dashoffset()
+                .ok_or_else(|| invalid("stroke-dashoffset", value))?;
+        }
+        for property in &["stroke-opacity", "fill-opacity", "opacity"] {
+            if let Some(value) = self.hash_style.get(*property) {
+                parse_alpha(value).ok_or_else(|| invalid(property, value))?;
+            }
+        }
+        match self.hash_style.get("stroke-dasharray") {
+            Some(value) if value != "none" && self.stroke_dasharray().is_none() => {
+                Err(invalid("stroke-dasharray", value))
+            }
+            _ => Ok(()),
         }
     }
 }
 
-impl From<&str> for SvgStyle {
-    fn from(style: &str) -> Self {
+impl SvgStyle {
+    /// Parse the declarations of a `style` attribute, failing on the first one that cannot be
+    /// parsed. Unlike `From<&str>`, which skips them.
+    ///
+    /// ```
+    /// use bevy_svg_map::{StyleError, SvgStyle};
+    /// use bevy::prelude::Color;
+    ///
+    /// let style = SvgStyle::parse("fill: url('data:image/png;base64,iVBO') red; stroke:blue;").unwrap();
+    /// assert_eq!(style.stroke(), Some(Color::BLUE));
+    /// assert_eq!(
+    ///     SvgStyle::parse("fill red").unwrap_err(),
+    ///     StyleError::InvalidDeclaration("fill red".to_owned())
+    /// );
+    /// ```
+    pub fn parse(style: &str) -> Result<Self, StyleError> {
+        let style = Self::from(style);
+        match style.warnings.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(style),
+        }
+    }
+    /// The declarations that were skipped because they could not be parsed
+    pub fn warnings(&self) -> &[StyleError] {
+        &self.warnings
+    }
+    /// Build the style from `(property, value)` pairs; the last value of a property wins.
+    fn from_declarations(declarations: impl IntoIterator<Item = (String, String)>) -> Self {
         SvgStyle {
-            hash_style: style
-                .split(';')
-                .map(|n| {
-                    let a: Vec<&str> = n.split(':').take(2).collect();
-                    (a[0].to_string(), a[1].to_string())
-                })
-                .collect::<HashMap<String, String>>(),
+            hash_style: declarations.into_iter().collect(),
             id: None,
             class: None,
+            layer: None,
+            displayed: true,
+            warnings: Vec::new(),
+            gradients: HashMap::new(),
+            units: Units::default(),
         }
     }
 }
 
+/// Parse the style leniently: the declarations that cannot be parsed are skipped and kept as
+/// [`SvgStyle::warnings`]
+impl From<&str> for SvgStyle {
+    fn from(style: &str) -> Self {
+        let (mut declarations, warnings) = parse_declarations(style);
+        declarations.sort_by_key(|d| d.important);
+        let mut style =
+            Self::from_declarations(declarations.into_iter().map(|d| (d.name, d.value)));
+        style.warnings = warnings;
+        style
+    }
+}
+
 impl Default for SvgStyle {
     fn default() -> Self {
         Self::from("fill:none;stroke:#000000;stroke-width:0.264583px;stroke-linecap:butt;stroke-linejoin:miter;stroke-opacity:1")
@@ -242,17 +552,26 @@ impl Default for SvgStyle {
 /// This trait is implemented by the user as the Strategy to add functionaly to the paths based
 /// on its properties (stored in `SvgStyle`).
 pub trait StyleStrategy {
+    /// Color of the material of the stroke
     fn color_decider(&self, _style: &SvgStyle) -> Color {
         Color::BLACK
     }
+    /// Color of the material of the fill
     fn color_fill_decider(&self, _style: &SvgStyle) -> Color {
         Color::BLACK
     }
+    /// Paint of the stroke, by default the color of `color_decider`. Gradients are drawn with
+    /// vertex colors, whatever `vertex_color_decider` says.
+    fn paint_decider(&self, style: &SvgStyle) -> Paint {
+        Paint::Color(self.color_decider(style))
+    }
+    /// Paint of the fill, by default the color of `color_fill_decider`. Gradients are drawn
+    /// with vertex colors, whatever `vertex_color_decider` says.
+    fn paint_fill_decider(&self, style: &SvgStyle) -> Paint {
+        Paint::Color(self.color_fill_decider(style))
+    }
     fn width_decider(&self, style: &SvgStyle) -> f32 {
-        match style.stroke_width() {
-            Some(c) => c,
-            _ => 0.264583,
-        }
+        style.stroke_width().unwrap_or(0.264583)
     }
     fn linecap_decider(&self, style: &SvgStyle) -> LineCap {
         match style.stroke_linecap() {
@@ -266,12 +585,74 @@ pub trait StyleStrategy {
             _ => LineJoin::Miter,
         }
     }
+    /// Rule that decides which parts of a path with holes or self intersections are filled
+    fn fill_rule_decider(&self, style: &SvgStyle) -> FillRule {
+        style.fill_rule().unwrap_or(FillRule::NonZero)
+    }
+    /// Options of the tessellation of the stroke. By default, the width, caps and join of the
+    /// other deciders and the `stroke-miterlimit`.
+    ///
+    /// The `line_width` is in the user units of the element, like `stroke-width`, and is then
+    /// scaled by its transform. The `bounds` of the element and the `tolerance` are in the
+    /// units of the map; the `tolerance` also flattens the curves of dashed strokes.
+    fn stroke_options_decider(&self, style: &SvgStyle, _bounds: &Rect) -> StrokeOptions {
+        StrokeOptions::default()
+            .with_line_width(self.width_decider(style))
+            .with_line_cap(self.linecap_decider(style))
+            .with_line_join(self.linejoin_decider(style))
+            .with_miter_limit(style.stroke_miterlimit().unwrap_or(4.))
+    }
+    /// Options of the tessellation of the fill, by default with the rule of
+    /// `fill_rule_decider`. The `bounds` of the element and the `tolerance` are in the units
+    /// of the map.
+    fn fill_options_decider(&self, style: &SvgStyle, _bounds: &Rect) -> FillOptions {
+        FillOptions::default().with_fill_rule(self.fill_rule_decider(style))
+    }
+    /// Lengths of the dashes and gaps of the stroke in user units, `None` for a solid line
+    fn dasharray_decider(&self, style: &SvgStyle) -> Option<Vec<f32>> {
+        style
+            .stroke_dasharray()
+            .map(|lengths| lengths.iter().map(|&l| l as f32).collect())
+    }
+    /// Offset into the dash pattern at which the stroke starts, in user units
+    fn dashoffset_decider(&self, style: &SvgStyle) -> f32 {
+        style.stroke_dashoffset().unwrap_or(0.)
+    }
+    /// World z of a part of the path. By default, the `z` that follows the paint order of the
+    /// document within [`MapOptions::z_range`](crate::MapOptions::z_range).
+    fn z_decider(&self, _style: &SvgStyle, _kind: PartKind, z: f32) -> f32 {
+        z
+    }
+    /// Whether the part is static and can be drawn by a single mesh together with the other
+    /// batched parts with the same material, see [`SvgMapBatch`](crate::SvgMapBatch). The
+    /// entity of a batched part is still spawned with its components, but it is not drawn.
+    fn batch_decider(&self, _style: &SvgStyle) -> bool {
+        false
+    }
+    /// Whether the color of the part is written to the vertices of its mesh, with a white
+    /// material shared by all of them, so that parts of different colors can be batched
+    /// together. The pipeline that draws them is registered by the
+    /// [`SvgMapPlugin`](crate::SvgMapPlugin).
+    fn vertex_color_decider(&self, _style: &SvgStyle) -> bool {
+        false
+    }
+    /// Components of a newly spawned part, e.g. colliders or gameplay state. It is not called
+    /// again for the parts that survive a reload of the map, see `component_reload_decider`.
     fn component_decider(
         &self,
         _style: &SvgStyle,
         _sprite: &mut bevy::ecs::system::EntityCommands,
     ) {
     }
+    /// Called instead of `component_decider` for a part whose entity is kept when its map is
+    /// reloaded by [`spawn_svg_maps`](crate::spawn_svg_maps), with the style of the new
+    /// document. The entity keeps all its components, so by default nothing is changed.
+    fn component_reload_decider(
+        &self,
+        _style: &SvgStyle,
+        _sprite: &mut bevy::ecs::system::EntityCommands,
+    ) {
+    }
 }
 
 #[cfg(test)]
@@ -289,4 +670,87 @@ mod tests {
         let style = SvgStyle::default();
         assert_eq!(style.stroke_width().unwrap(), 0.264583);
     }
+
+    #[test]
+    fn paint_order() {
+        let order = |s: &str| SvgStyle::from(s).paint_order();
+        assert_eq!(order(""), [PartKind::Fill, PartKind::Stroke]);
+        assert_eq!(
+            order("paint-order:stroke"),
+            [PartKind::Stroke, PartKind::Fill]
+        );
+        assert_eq!(
+            order("paint-order:markers fill stroke"),
+            [PartKind::Fill, PartKind::Stroke]
+        );
+        assert_eq!(
+            order("paint-order:markers stroke"),
+            [PartKind::Stroke, PartKind::Fill]
+        );
+    }
+
+    #[test]
+    fn opacity_is_alpha() {
+        let style = SvgStyle::from("fill:#0000ff;fill-opacity:0.5;stroke:#ff0000;stroke-opacity:0");
+        assert_eq!(style.fill(), Some(Color::rgba(0., 0., 1., 0.5)));
+        assert_eq!(style.stroke(), Some(Color::rgba(1., 0., 0., 0.)));
+        let style = SvgStyle::from("fill:#0000ff80;fill-opacity:0.5;opacity:50%");
+        assert_eq!(style.fill().unwrap().a(), 128. / 255. * 0.25);
+        // an invalid opacity is `None` and does not change the paint
+        let style = SvgStyle::from("fill:#0000ff;fill-opacity:NaN;stroke-opacity:half");
+        assert_eq!((style.fill_opacity(), style.stroke_opacity()), (None, None));
+        assert_eq!(style.fill(), Some(Color::BLUE));
+        assert_eq!(SvgStyle::from("fill:red").fill_opacity(), Some(1.));
+    }
+
+    #[test]
+    fn missing_properties_do_not_panic() {
+        let style = SvgStyle::from("stroke-dasharray:none;;bogus");
+        assert!(style.stroke().is_none());
+        assert!(style.stroke_width().is_none());
+        assert!(style.stroke_dasharray().is_none());
+        assert!(style.validate().is_ok());
+    }
+
+    #[test]
+    fn dasharray_with_units() {
+        let dashes = |value: &str| {
+            let mut style = SvgStyle::from(format!("stroke-dasharray:{}", value).as_str());
+            style.units.view_box = (300., 400.);
+            assert!(style.validate().is_ok());
+            style.stroke_dasharray().unwrap().0
+        };
+        let close = |value: &str, expected: &[f64]| {
+            let dashes = dashes(value);
+            dashes.len() == expected.len()
+                && dashes
+                    .iter()
+                    .zip(expected)
+                    .all(|(d, e)| (d - e).abs() < 1e-4)
+        };
+        assert_eq!(dashes("5px, 5px"), [5., 5.]);
+        assert_eq!(dashes("1in 0.5in"), [96., 48.]);
+        assert!(close("1mm 2mm", &[96. / 25.4, 2. * 96. / 25.4]));
+        // percentages of the normalized diagonal of the viewBox
+        assert!(close("10%", &[(125000f64).sqrt() / 10.]));
+        assert!(SvgStyle::from("stroke-dasharray:5 apples")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn miterlimit_below_one_is_invalid() {
+        let style = SvgStyle::from("stroke:#000000;stroke-miterlimit:0.5");
+        assert!(style.stroke_miterlimit().is_none());
+        assert!(style.validate().is_err());
+    }
+
+    #[test]
+    fn invalid_style_is_an_error() {
+        let style = SvgStyle::from("stroke:#000000;stroke-width:thick");
+        assert!(matches!(
+            style.validate(),
+            Err(SvgMapError::StyleSyntax { property, .. }) if property == "stroke-width"
+        ));
+    }
 }
@@ -0,0 +1,93 @@
+//! Handling of the `transform` attribute of SVG elements.
+use bevy::prelude::{Quat, Transform, Vec3};
+use euclid::default::Transform2D;
+use std::str::FromStr;
+
+/// Parse a [transform list](https://www.w3.org/TR/SVG11/coords.html#TransformAttribute)
+/// (translate, scale, rotate, skewX, skewY and matrix) into an euclid `Transform2D`.
+/// A malformed list is ignored, as if there was no transform.
+pub fn parse_transform(value: &str) -> Transform2D<f32> {
+    match svgtypes::Transform::from_str(value) {
+        Ok(svgtypes::Transform { a, b, c, d, e, f }) => {
+            Transform2D::new(a as f32, b as f32, c as f32, d as f32, e as f32, f as f32)
+        }
+        _ => Transform2D::identity(),
+    }
+}
+
+/// Compose the transform of the node with the ones of all its ancestors, mapping
+/// the user space of the node to the user space of the root `<svg>`.
+pub fn node_transform(node: &roxmltree::Node) -> Transform2D<f32> {
+    node.ancestors()
+        .filter_map(|n| n.attribute("transform"))
+        .fold(Transform2D::identity(), |acc, t| {
+            acc.then(&parse_transform(t))
+        })
+}
+
+/// Scale factor of the transform, used to scale magnitudes like the stroke width.
+pub fn mean_scale(transform: &Transform2D<f32>) -> f32 {
+    transform.determinant().abs().sqrt()
+}
+
+/// Decompose the transform into the translation, rotation and scale of a bevy `Transform`.
+/// Returns `None` if it is skewed or degenerate, since bevy cannot represent it.
+pub fn decompose(t: &Transform2D<f32>) -> Option<Transform> {
+    const EPSILON: f32 = 1e-5;
+    let sx = t.m11.hypot(t.m12);
+    let angle = t.m12.atan2(t.m11);
+    let (sin, cos) = angle.sin_cos();
+    // the second column is (0, sy) rotated, any component along the first one is skew
+    let sy = -t.m21 * sin + t.m22 * cos;
+    let skew = t.m21 * cos + t.m22 * sin;
+    if sx < EPSILON || sy.abs() < EPSILON || skew.abs() > EPSILON * sx.max(sy.abs()) {
+        return None;
+    }
+    Some(Transform {
+        translation: Vec3::new(t.m31, t.m32, 0.),
+        rotation: Quat::from_rotation_z(angle),
+        scale: Vec3::new(sx, sy, 1.),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use euclid::default::Point2D;
+
+    #[test]
+    fn transform_list_order() {
+        let t = parse_transform("translate(10,0) scale(2)");
+        assert_eq!(
+            t.transform_point(Point2D::new(1., 1.)),
+            Point2D::new(12., 2.)
+        );
+    }
+
+    #[test]
+    fn compose_ancestors() {
+        let doc = roxmltree::Document::parse(
+            r#"<svg><g transform="translate(-5,3)"><g transform="scale(2)"><path d="M 0 0" transform="translate(1,1)"/></g></g></svg>"#,
+        )
+        .unwrap();
+        let path = doc.descendants().find(|n| n.has_tag_name("path")).unwrap();
+        assert_eq!(
+            node_transform(&path).transform_point(Point2D::new(0., 0.)),
+            Point2D::new(-3., 5.)
+        );
+    }
+
+    #[test]
+    fn decompose_transform() {
+        let t = parse_transform("translate(1,2) rotate(90) scale(2,-3)");
+        let decomposed = decompose(&t).unwrap();
+        // as rendered, the scale is applied before the rotation
+        let p = decomposed
+            .compute_matrix()
+            .transform_point3(Vec3::new(1., 1., 0.));
+        let expected = t.transform_point(Point2D::new(1., 1.));
+        assert!((p.x - expected.x).abs() < 1e-5 && (p.y - expected.y).abs() < 1e-5);
+        assert!(decompose(&parse_transform("skewX(30)")).is_none());
+        assert!(decompose(&parse_transform("scale(0)")).is_none());
+    }
+}
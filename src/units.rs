@@ -0,0 +1,123 @@
+//! Conversion of SVG lengths into user units.
+//!
+//! A user unit is a CSS pixel, so absolute units are converted with the DPI (96 in CSS,
+//! but older Inkscape versions used 90) and relative units need some context: `em`/`ex`
+//! take the font size and percentages refer to the size of the viewBox.
+use std::str::FromStr;
+use svgtypes::{Length, LengthUnit, ViewBox};
+
+/// Dimension a length refers to, used to resolve percentages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    /// Horizontal lengths (x, width, rx...).
+    X,
+    /// Vertical lengths (y, height, ry...).
+    Y,
+    /// Lengths that are not tied to an axis (stroke-width, r...).
+    Other,
+}
+
+/// Context to convert lengths into user units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Units {
+    /// Pixels in one inch.
+    pub dpi: f32,
+    /// Font size in pixels, reference of `em` and `ex`.
+    pub font_size: f32,
+    /// Size of the viewBox in user units, reference of percentages.
+    pub view_box: (f32, f32),
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Units {
+            dpi: 96.,
+            font_size: 16.,
+            view_box: (0., 0.),
+        }
+    }
+}
+
+impl Units {
+    /// Read the context from the root `<svg>` element.
+    pub fn from_root(root: &roxmltree::Node, dpi: f32) -> Self {
+        let units = Units {
+            dpi,
+            ..Default::default()
+        };
+        let view_box = match root
+            .attribute("viewBox")
+            .and_then(|v| ViewBox::from_str(v).ok())
+        {
+            Some(vb) => (vb.w as f32, vb.h as f32),
+            None => (
+                root.attribute("width")
+                    .and_then(|v| units.parse(v, Axis::X))
+                    .unwrap_or(0.),
+                root.attribute("height")
+                    .and_then(|v| units.parse(v, Axis::Y))
+                    .unwrap_or(0.),
+            ),
+        };
+        Units { view_box, ..units }
+    }
+
+    /// Convert a length into user units.
+    pub fn length(&self, Length { num, unit }: Length, axis: Axis) -> f32 {
+        let num = num as f32;
+        match unit {
+            LengthUnit::None | LengthUnit::Px => num,
+            LengthUnit::In => num * self.dpi,
+            LengthUnit::Cm => num * self.dpi / 2.54,
+            LengthUnit::Mm => num * self.dpi / 25.4,
+            LengthUnit::Pt => num * self.dpi / 72.,
+            LengthUnit::Pc => num * self.dpi / 6.,
+            LengthUnit::Em => num * self.font_size,
+            LengthUnit::Ex => num * self.font_size / 2.,
+            LengthUnit::Percent => {
+                let (w, h) = self.view_box;
+                num / 100.
+                    * match axis {
+                        Axis::X => w,
+                        Axis::Y => h,
+                        // normalized diagonal, see https://www.w3.org/TR/SVG11/coords.html#Units
+                        Axis::Other => ((w * w + h * h) / 2.).sqrt(),
+                    }
+            }
+        }
+    }
+
+    /// Parse a length and convert it into user units.
+    pub fn parse(&self, value: &str, axis: Axis) -> Option<f32> {
+        Length::from_str(value)
+            .ok()
+            .map(|length| self.length(length, axis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_units() {
+        let units = Units::default();
+        assert_eq!(units.parse("1in", Axis::X), Some(96.));
+        assert_eq!(units.parse("2.54cm", Axis::X), Some(96.));
+        assert_eq!(units.parse("72pt", Axis::Other), Some(96.));
+        assert_eq!(units.parse("12", Axis::Y), Some(12.));
+    }
+
+    #[test]
+    fn relative_units() {
+        let units = Units {
+            dpi: 90.,
+            view_box: (200., 100.),
+            ..Default::default()
+        };
+        assert_eq!(units.parse("25.4mm", Axis::X), Some(90.));
+        assert_eq!(units.parse("50%", Axis::X), Some(100.));
+        assert_eq!(units.parse("50%", Axis::Y), Some(50.));
+        assert_eq!(units.parse("2em", Axis::Other), Some(32.));
+    }
+}
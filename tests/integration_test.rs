@@ -1,4 +1,9 @@
-use bevy_svg_map::{load_svg_map, StyleStrategy, SvgStyle};
+use bevy_svg_map::{
+    load_svg_map, load_svg_map_from_bytes, load_svg_map_from_str, spawn_svg_maps, ElementKey,
+    HiddenElements, MapOptions, Paint, PartKind, StyleStrategy, SvgLayer, SvgMap, SvgMapBatch,
+    SvgMapCache, SvgMapGroup, SvgMapPart, SvgMapPlugin, SvgMapRoot, SvgStyle,
+    VERTEX_COLOR_PIPELINE_HANDLE,
+};
 
 use bevy::{ecs::system::EntityCommands, prelude::*};
 
@@ -33,10 +38,7 @@ impl StyleStrategy for CustomStrategy {
     }
 
     fn width_decider(&self, style: &SvgStyle) -> f32 {
-        match style.stroke_width() {
-            Some(c) => c,
-            _ => 0.264583,
-        }
+        style.stroke_width().unwrap_or(0.264583)
     }
 
     fn linecap_decider(&self, style: &SvgStyle) -> lyon::lyon_tessellation::LineCap {
@@ -66,7 +68,7 @@ fn setup(
     materials: ResMut<Assets<ColorMaterial>>,
     meshes: ResMut<Assets<Mesh>>,
 ) {
-    load_svg_map(commands, materials, meshes, "assets/ex.svg", MyStrategy);
+    load_svg_map(commands, materials, meshes, "assets/ex.svg", MyStrategy).unwrap();
 }
 
 fn setup_custom(
@@ -74,7 +76,41 @@ fn setup_custom(
     materials: ResMut<Assets<ColorMaterial>>,
     meshes: ResMut<Assets<Mesh>>,
 ) {
-    load_svg_map(commands, materials, meshes, "assets/ex.svg", CustomStrategy);
+    load_svg_map(commands, materials, meshes, "assets/ex.svg", CustomStrategy).unwrap();
+}
+
+fn setup_from_str(
+    commands: Commands,
+    materials: ResMut<Assets<ColorMaterial>>,
+    meshes: ResMut<Assets<Mesh>>,
+) {
+    let svg = include_str!("../assets/ex.svg");
+    load_svg_map_from_str(
+        commands,
+        materials,
+        meshes,
+        svg,
+        MyStrategy,
+        &MapOptions::default(),
+    )
+    .unwrap();
+}
+
+fn setup_from_bytes(
+    commands: Commands,
+    materials: ResMut<Assets<ColorMaterial>>,
+    meshes: ResMut<Assets<Mesh>>,
+) {
+    let svg = include_bytes!("../assets/ex.svg");
+    load_svg_map_from_bytes(
+        commands,
+        materials,
+        meshes,
+        svg,
+        MyStrategy,
+        &MapOptions::default(),
+    )
+    .unwrap();
 }
 
 #[test]
@@ -86,3 +122,568 @@ fn can_it_be_added() {
 fn custom_style_strategy() {
     App::build().add_startup_system(setup_custom.system());
 }
+
+#[test]
+fn embedded_maps() {
+    let mut builder = App::build();
+    builder
+        .add_plugin(bevy::core::CorePlugin)
+        .add_plugin(bevy::asset::AssetPlugin)
+        .add_asset::<Mesh>()
+        .add_asset::<ColorMaterial>()
+        .add_startup_system(setup_from_str.system())
+        .add_startup_system(setup_from_bytes.system());
+    let mut app = builder.app;
+    app.update();
+
+    let map = SvgMap::parse(include_str!("../assets/ex.svg"), &MapOptions::default()).unwrap();
+    let parts: usize = map.elements().iter().map(|e| e.parts().len()).sum();
+    assert!(parts > 0);
+    let world = &mut app.world;
+    assert_eq!(world.query::<&SvgMapRoot>().iter(world).count(), 2);
+    assert_eq!(world.query::<&SvgMapPart>().iter(world).count(), 2 * parts);
+}
+
+#[test]
+fn asset_plugin() {
+    let mut builder = App::build();
+    builder
+        .add_plugin(bevy::core::CorePlugin)
+        .add_plugin(bevy::asset::AssetPlugin)
+        .add_plugin(SvgMapPlugin::default());
+    let mut app = builder.app;
+    let handle: Handle<SvgMap> = app
+        .world
+        .get_resource::<AssetServer>()
+        .unwrap()
+        .load("ex.svg");
+    // the asset is read and parsed in the background
+    for _ in 0..500 {
+        app.update();
+        let state = app
+            .world
+            .get_resource::<AssetServer>()
+            .unwrap()
+            .get_load_state(&handle);
+        assert_ne!(state, bevy::asset::LoadState::Failed);
+        if state == bevy::asset::LoadState::Loaded {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let maps = app.world.get_resource::<Assets<SvgMap>>().unwrap();
+    let map = maps.get(&handle).expect("the map is loaded");
+    let expected = SvgMap::parse(include_str!("../assets/ex.svg"), &MapOptions::default()).unwrap();
+    assert!(!map.elements().is_empty());
+    assert_eq!(map.elements().len(), expected.elements().len());
+    assert_eq!(map.groups().len(), expected.groups().len());
+}
+
+fn map_app() -> App {
+    map_app_with(MyStrategy)
+}
+
+fn map_app_with<T: StyleStrategy + Send + Sync + 'static>(strategy: T) -> App {
+    let mut builder = App::build();
+    builder
+        .add_plugin(bevy::core::CorePlugin)
+        .add_plugin(bevy::asset::AssetPlugin)
+        .add_plugin(SvgMapPlugin::default())
+        .add_asset::<Mesh>()
+        .add_asset::<ColorMaterial>()
+        .insert_resource(strategy)
+        .add_system(spawn_svg_maps::<T>.system());
+    builder.app
+}
+
+/// Add the map parsed from `svg` to the assets, spawn it and run the systems once
+fn spawn_map(app: &mut App, svg: &str, options: &MapOptions) -> Handle<SvgMap> {
+    let map = SvgMap::parse(svg, options).unwrap();
+    let handle = app
+        .world
+        .get_resource_mut::<Assets<SvgMap>>()
+        .unwrap()
+        .add(map);
+    app.world.spawn().insert(handle.clone());
+    app.update();
+    handle
+}
+
+#[test]
+fn maps_are_respawned_on_change() {
+    let mut app = map_app();
+    let parts = |world: &mut World| world.query::<&SvgMapPart>().iter(world).count();
+    let parse = |svg: &str| SvgMap::parse(svg, &MapOptions::default()).unwrap();
+
+    let handle = spawn_map(
+        &mut app,
+        include_str!("../assets/with_shapes.svg"),
+        &MapOptions::default(),
+    );
+    let spawned = parts(&mut app.world);
+    assert!(spawned > 0);
+
+    // editing the file replaces the asset
+    let _handle = app.world.get_resource_mut::<Assets<SvgMap>>().unwrap().set(
+        handle,
+        parse(r#"<svg><rect width="1" height="1" fill="red"/></svg>"#),
+    );
+    app.update();
+    app.update();
+    assert_eq!(parts(&mut app.world), 1);
+}
+
+struct Health(u32);
+
+struct Reloaded;
+
+struct GameplayStrategy;
+
+impl StyleStrategy for GameplayStrategy {
+    fn component_decider(&self, _style: &SvgStyle, comp: &mut EntityCommands) {
+        comp.insert(Health(10));
+    }
+    fn component_reload_decider(&self, _style: &SvgStyle, comp: &mut EntityCommands) {
+        comp.insert(Reloaded);
+    }
+}
+
+#[test]
+fn entities_survive_reload() {
+    let mut app = map_app_with(GameplayStrategy);
+    let parse = |svg: &str| SvgMap::parse(svg, &MapOptions::default()).unwrap();
+    let find = |world: &mut World, id: &str| {
+        world
+            .query::<(Entity, &SvgMapPart)>()
+            .iter(world)
+            .find(|(_, part)| part.key == ElementKey::Id(id.to_owned()))
+            .map(|(entity, _)| entity)
+    };
+    let handle = spawn_map(
+        &mut app,
+        r#"<svg><rect id="door" width="1" height="1" fill="red"/>
+           <rect id="wall" width="2" height="1" fill="red"/></svg>"#,
+        &MapOptions::default(),
+    );
+    let door = find(&mut app.world, "door").unwrap();
+    app.world.entity_mut(door).insert(Health(3));
+
+    let _handle = app.world.get_resource_mut::<Assets<SvgMap>>().unwrap().set(
+        handle,
+        parse(
+            r#"<svg><rect id="door" width="1" height="1" fill="blue"/>
+               <rect id="key" width="1" height="1" fill="red"/></svg>"#,
+        ),
+    );
+    app.update();
+    app.update();
+    assert_eq!(find(&mut app.world, "door"), Some(door));
+    assert_eq!(app.world.get::<Health>(door).unwrap().0, 3);
+    assert!(app.world.get::<Reloaded>(door).is_some());
+    assert!(find(&mut app.world, "wall").is_none());
+    let key = find(&mut app.world, "key").unwrap();
+    assert_eq!(app.world.get::<Health>(key).unwrap().0, 10);
+}
+
+#[test]
+fn hierarchy_mirrors_groups() {
+    let mut app = map_app();
+    let parse = |svg: &str| SvgMap::parse(svg, &MapOptions::default()).unwrap();
+    let parent = |world: &mut World, id: &str| {
+        let key = ElementKey::Id(id.to_owned());
+        let entity = world
+            .query::<(Entity, Option<&SvgMapPart>, Option<&SvgMapGroup>)>()
+            .iter(world)
+            .find(|(_, part, group)| {
+                part.map(|p| &p.key) == Some(&key) || group.map(|g| &g.key) == Some(&key)
+            })
+            .unwrap()
+            .0;
+        world.get::<Parent>(entity).unwrap().0
+    };
+    let handle = spawn_map(
+        &mut app,
+        r#"<svg><g id="layer" transform="translate(5 0)"><g id="inner">
+           <rect id="a" width="1" height="1" fill="red"/></g></g>
+           <rect id="b" width="1" height="1" fill="red"/></svg>"#,
+        &MapOptions::default(),
+    );
+    let (root, _) = app
+        .world
+        .query::<(Entity, &SvgMapRoot)>()
+        .iter(&app.world)
+        .next()
+        .unwrap();
+    let layer = parent(&mut app.world, "inner");
+    assert_eq!(parent(&mut app.world, "layer"), root);
+    assert_eq!(parent(&mut app.world, "b"), root);
+    assert_eq!(
+        app.world.get::<Transform>(layer).unwrap().translation,
+        Vec3::new(5., 0., 0.)
+    );
+
+    // "a" is moved out of the groups
+    let _handle = app.world.get_resource_mut::<Assets<SvgMap>>().unwrap().set(
+        handle,
+        parse(r#"<svg><g id="layer"/><rect id="a" width="1" height="1" fill="red"/></svg>"#),
+    );
+    app.update();
+    app.update();
+    assert_eq!(parent(&mut app.world, "a"), root);
+    assert_eq!(app.world.get::<Children>(layer).map(|c| c.len()), Some(0));
+    assert_eq!(app.world.get::<Children>(root).unwrap().len(), 2);
+}
+
+#[test]
+fn layers_are_components() {
+    let mut app = map_app();
+    spawn_map(
+        &mut app,
+        include_str!("../assets/ex.svg"),
+        &MapOptions::default(),
+    );
+    let layers: Vec<SvgLayer> = app
+        .world
+        .query::<(&SvgLayer, &SvgMapGroup)>()
+        .iter(&app.world)
+        .map(|(layer, _)| layer.clone())
+        .collect();
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers[0].index, 0);
+}
+
+struct SvgColors;
+
+impl StyleStrategy for SvgColors {
+    fn color_decider(&self, style: &SvgStyle) -> Color {
+        style.stroke().unwrap()
+    }
+    fn color_fill_decider(&self, style: &SvgStyle) -> Color {
+        style.fill().unwrap()
+    }
+}
+
+#[test]
+fn fills_and_strokes_have_their_own_material() {
+    let mut app = map_app_with(SvgColors);
+    spawn_map(
+        &mut app,
+        r#"<svg><rect width="1" height="1" fill="blue" stroke="red"/></svg>"#,
+        &MapOptions::default(),
+    );
+    let parts: Vec<(PartKind, Handle<ColorMaterial>)> = app
+        .world
+        .query::<(&SvgMapPart, &Handle<ColorMaterial>)>()
+        .iter(&app.world)
+        .map(|(part, material)| (part.kind, material.clone()))
+        .collect();
+    let materials = app.world.get_resource::<Assets<ColorMaterial>>().unwrap();
+    let color = |kind| {
+        let (_, handle) = parts.iter().find(|(k, _)| *k == kind).unwrap();
+        materials.get(handle).unwrap().color
+    };
+    assert_eq!(color(PartKind::Fill), Color::BLUE);
+    assert_eq!(color(PartKind::Stroke), Color::RED);
+}
+
+#[test]
+fn materials_and_meshes_are_shared() {
+    let mut app = map_app();
+    app.world.insert_resource(SvgMapCache::default());
+    let handle = spawn_map(
+        &mut app,
+        r#"<svg><rect width="1" height="1" fill="red"/>
+               <rect x="5" width="1" height="1" fill="red"/>
+               <rect width="2" height="1" fill="red"/></svg>"#,
+        &MapOptions::default(),
+    );
+    app.world.spawn().insert(handle);
+    app.update();
+    assert_eq!(app.world.query::<&SvgMapPart>().iter(&app.world).count(), 6);
+    let materials = app.world.get_resource::<Assets<ColorMaterial>>().unwrap();
+    let meshes = app.world.get_resource::<Assets<Mesh>>().unwrap();
+    assert_eq!((materials.len(), meshes.len()), (1, 2));
+    let cache = app.world.get_resource::<SvgMapCache>().unwrap();
+    assert_eq!((cache.materials(), cache.meshes()), (1, 2));
+}
+
+struct Batching;
+
+impl StyleStrategy for Batching {
+    fn color_fill_decider(&self, style: &SvgStyle) -> Color {
+        style.fill().unwrap()
+    }
+    fn batch_decider(&self, _style: &SvgStyle) -> bool {
+        true
+    }
+}
+
+#[test]
+fn static_parts_are_batched() {
+    let mut app = map_app_with(Batching);
+    let parse = |svg: &str| SvgMap::parse(svg, &MapOptions::default()).unwrap();
+    let handle = spawn_map(
+        &mut app,
+        r#"<svg><rect width="1" height="1" fill="red"/>
+           <g transform="translate(5 0)"><rect width="1" height="1" fill="red"/></g>
+           <rect id="door" width="1" height="1" fill="blue"/></svg>"#,
+        &MapOptions::default(),
+    );
+    let batches = |world: &mut World| {
+        world
+            .query::<(&SvgMapBatch, &Handle<Mesh>)>()
+            .iter(world)
+            .map(|(_, mesh)| mesh.clone())
+            .collect::<Vec<_>>()
+    };
+    let meshes = batches(&mut app.world);
+    assert_eq!(meshes.len(), 2);
+    // the logic entities are still there, without a mesh
+    assert_eq!(
+        app.world
+            .query::<(&SvgMapPart, Option<&Handle<Mesh>>)>()
+            .iter(&app.world)
+            .filter(|(_, mesh)| mesh.is_none())
+            .count(),
+        3
+    );
+    let assets = app.world.get_resource::<Assets<Mesh>>().unwrap();
+    let vertices: Vec<usize> = meshes
+        .iter()
+        .map(|m| assets.get(m).unwrap().count_vertices())
+        .collect();
+    assert!(vertices.contains(&(2 * vertices.iter().min().unwrap())));
+
+    let _handle = app.world.get_resource_mut::<Assets<SvgMap>>().unwrap().set(
+        handle,
+        parse(r#"<svg><rect id="door" width="1" height="1" fill="blue"/></svg>"#),
+    );
+    app.update();
+    app.update();
+    assert_eq!(batches(&mut app.world).len(), 1);
+}
+
+struct BatchStatic;
+
+impl StyleStrategy for BatchStatic {
+    fn batch_decider(&self, style: &SvgStyle) -> bool {
+        style.class().as_deref() != Some("dynamic")
+    }
+}
+
+#[test]
+fn reused_parts_can_be_batched() {
+    let mut app = map_app_with(BatchStatic);
+    let parse = |svg: &str| SvgMap::parse(svg, &MapOptions::default()).unwrap();
+    let handle = spawn_map(
+        &mut app,
+        r#"<svg><rect id="door" class="dynamic" width="1" height="1" fill="blue"/></svg>"#,
+        &MapOptions::default(),
+    );
+    let door = app
+        .world
+        .query::<(Entity, &SvgMapPart)>()
+        .iter(&app.world)
+        .next()
+        .unwrap()
+        .0;
+    assert!(app.world.get::<Sprite>(door).is_some());
+
+    let _handle = app.world.get_resource_mut::<Assets<SvgMap>>().unwrap().set(
+        handle,
+        parse(r#"<svg><rect id="door" width="1" height="1" fill="blue"/></svg>"#),
+    );
+    app.update();
+    app.update();
+    // the entity is kept, like a freshly spawned batched part
+    let entity = app.world.entity(door);
+    assert!(entity.get::<SvgMapPart>().is_some());
+    assert!(entity.get::<Transform>().is_some());
+    assert!(entity.get::<Sprite>().is_none());
+    assert!(entity.get::<Handle<Mesh>>().is_none());
+    assert!(entity.get::<Handle<ColorMaterial>>().is_none());
+    assert!(entity.get::<Draw>().is_none());
+    assert!(entity.get::<Visible>().is_none());
+    assert!(entity.get::<RenderPipelines>().is_none());
+    assert!(entity
+        .get::<bevy::render::render_graph::base::MainPass>()
+        .is_none());
+}
+
+struct VertexColors;
+
+impl StyleStrategy for VertexColors {
+    fn color_fill_decider(&self, style: &SvgStyle) -> Color {
+        style.fill().unwrap()
+    }
+    fn batch_decider(&self, _style: &SvgStyle) -> bool {
+        true
+    }
+    fn vertex_color_decider(&self, _style: &SvgStyle) -> bool {
+        true
+    }
+}
+
+#[test]
+fn vertex_colors_share_a_batch() {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    let mut app = map_app_with(VertexColors);
+    spawn_map(
+        &mut app,
+        r#"<svg><rect width="1" height="1" fill="red"/>
+               <rect x="2" width="1" height="1" fill="blue"/></svg>"#,
+        &MapOptions::default(),
+    );
+    let batches: Vec<(Handle<Mesh>, RenderPipelines)> = app
+        .world
+        .query::<(&SvgMapBatch, &Handle<Mesh>, &RenderPipelines)>()
+        .iter(&app.world)
+        .map(|(_, mesh, pipelines)| (mesh.clone(), pipelines.clone()))
+        .collect();
+    assert_eq!(batches.len(), 1);
+    let (mesh, pipelines) = &batches[0];
+    assert_eq!(
+        pipelines.pipelines[0].pipeline,
+        VERTEX_COLOR_PIPELINE_HANDLE.typed()
+    );
+    let mesh = app
+        .world
+        .get_resource::<Assets<Mesh>>()
+        .unwrap()
+        .get(mesh)
+        .unwrap();
+    match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+        Some(VertexAttributeValues::Float4(colors)) => {
+            assert!(colors.contains(&Color::RED.as_linear_rgba_f32()));
+            assert!(colors.contains(&Color::BLUE.as_linear_rgba_f32()));
+        }
+        _ => panic!("the batch has no vertex colors"),
+    }
+    match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float3(normals)) => {
+            assert!(normals.iter().all(|n| n == &[0., 0., 1.]))
+        }
+        _ => panic!("the batch has no normals"),
+    }
+}
+
+struct Gradients;
+
+impl StyleStrategy for Gradients {
+    fn paint_fill_decider(&self, style: &SvgStyle) -> Paint {
+        style.fill_paint().unwrap()
+    }
+}
+
+#[test]
+fn gradients_are_vertex_colors() {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    // the vertex colors of each part of the map
+    fn vertex_colors(svg: &str) -> Vec<Vec<[f32; 4]>> {
+        let mut app = map_app_with(Gradients);
+        spawn_map(&mut app, svg, &MapOptions::default());
+        let parts: Vec<(Handle<Mesh>, RenderPipelines)> = app
+            .world
+            .query::<(&SvgMapPart, &Handle<Mesh>, &RenderPipelines)>()
+            .iter(&app.world)
+            .map(|(_, mesh, pipelines)| (mesh.clone(), pipelines.clone()))
+            .collect();
+        let meshes = app.world.get_resource::<Assets<Mesh>>().unwrap();
+        parts
+            .iter()
+            .map(|(mesh, pipelines)| {
+                assert_eq!(
+                    pipelines.pipelines[0].pipeline,
+                    VERTEX_COLOR_PIPELINE_HANDLE.typed()
+                );
+                match meshes.get(mesh).unwrap().attribute(Mesh::ATTRIBUTE_COLOR) {
+                    Some(VertexAttributeValues::Float4(colors)) => colors.clone(),
+                    _ => panic!("the gradient has no vertex colors"),
+                }
+            })
+            .collect()
+    }
+    let any = |colors: &[[f32; 4]], channel: usize| colors.iter().any(|c| c[channel] > 0.8);
+
+    let parts = vertex_colors(include_str!("../assets/with_gradients.svg"));
+    assert_eq!(parts.len(), 2);
+    // the pond is half transparent, red at its center and blue at its edge
+    let pond = parts.iter().find(|colors| colors[0][3] == 0.5).unwrap();
+    assert!(any(pond, 0) && any(pond, 2));
+
+    // the middle stop shows up inside the rect, whose corners are all at the first stop
+    let parts = vertex_colors(
+        r##"<svg><linearGradient id="g" x2="0.5" spreadMethod="repeat">
+            <stop offset="0" stop-color="red"/><stop offset="0.5" stop-color="lime"/>
+            <stop offset="1" stop-color="blue"/></linearGradient>
+            <rect width="10" height="10" fill="url(#g)"/></svg>"##,
+    );
+    assert_eq!(parts.len(), 1);
+    assert!(any(&parts[0], 0) && any(&parts[0], 1) && any(&parts[0], 2));
+}
+
+struct Coarse;
+
+impl StyleStrategy for Coarse {
+    fn stroke_options_decider(
+        &self,
+        style: &SvgStyle,
+        bounds: &lyon::math::Rect,
+    ) -> lyon::lyon_tessellation::StrokeOptions {
+        // a tolerance relative to the size of the element
+        lyon::lyon_tessellation::StrokeOptions::tolerance(bounds.width() / 4.)
+            .with_line_width(self.width_decider(style))
+    }
+}
+
+#[test]
+fn stroke_options_follow_the_strategy() {
+    fn vertices<T: StyleStrategy + Send + Sync + 'static>(strategy: T) -> usize {
+        let mut app = map_app_with(strategy);
+        spawn_map(
+            &mut app,
+            r#"<svg><circle r="50" stroke="black" fill="none"/></svg>"#,
+            &MapOptions::default(),
+        );
+        let mesh = app
+            .world
+            .query::<(&SvgMapPart, &Handle<Mesh>)>()
+            .iter(&app.world)
+            .map(|(_, mesh)| mesh.clone())
+            .next()
+            .unwrap();
+        app.world
+            .get_resource::<Assets<Mesh>>()
+            .unwrap()
+            .get(mesh)
+            .unwrap()
+            .count_vertices()
+    }
+    assert!(vertices(Coarse) < vertices(MyStrategy));
+}
+
+#[test]
+fn hidden_elements_are_invisible() {
+    let mut app = map_app();
+    let options = MapOptions {
+        hidden: HiddenElements::Invisible,
+        ..Default::default()
+    };
+    spawn_map(
+        &mut app,
+        r#"<svg fill="none"><path d="M 0 0 H 1" stroke="red" display="none"/>
+               <path d="M 0 1 H 1" stroke="red"/></svg>"#,
+        &options,
+    );
+    let mut visible: Vec<bool> = app
+        .world
+        .query::<(&SvgMapPart, &Visible)>()
+        .iter(&app.world)
+        .map(|(_, visible)| visible.is_visible)
+        .collect();
+    visible.sort_unstable();
+    assert_eq!(visible, [false, true]);
+}